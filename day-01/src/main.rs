@@ -1,3 +1,9 @@
+use std::fmt;
+
+// Not needed for the puzzle answers
+#[cfg_attr(not(test), expect(dead_code))]
+mod odometer;
+
 const INPUT: &str = include_str!("../input.txt");
 
 fn main() {
@@ -21,85 +27,261 @@ const DIAL_SIZE: u8 = 100;
 
 fn password(s: &str) -> usize {
     let mut dial = u32::from(DIAL_START);
-    spins(s)
-        .map(|n| {
-            let n = n.rem_euclid(DIAL_SIZE.into());
-            dial = dial.strict_add_signed(n);
-            dial.rem_euclid(DIAL_SIZE.into())
+    instructions(s)
+        .filter(|&i| match i {
+            Instruction::Rotate(n) => {
+                let n = n.rem_euclid(DIAL_SIZE.into());
+                dial = dial.strict_add_signed(n);
+                dial = dial.rem_euclid(DIAL_SIZE.into());
+                dial == 0
+            }
+            // Setting the dial is not a rotation, so landing on zero
+            // this way does not count.
+            Instruction::Set(n) => {
+                dial = n;
+                false
+            }
         })
-        .filter(|&d| d == 0)
         .count()
 }
 
 fn password_0x434c49434b(s: &str) -> usize {
-    password_0x434c49434b_core(DIAL_START.into(), spins(s))
+    password_0x434c49434b_core(DIAL_START.into(), instructions(s))
+}
+
+fn password_0x434c49434b_core(
+    mut dial: u32,
+    instructions: impl IntoIterator<Item = impl Into<Instruction>>,
+) -> usize {
+    instructions
+        .into_iter()
+        .map(|i| turn(&mut dial, i.into()))
+        .sum()
 }
 
-fn password_0x434c49434b_core(mut dial: u32, directions: impl IntoIterator<Item = i32>) -> usize {
+/// Applies one instruction to the dial, returning the number of times
+/// it pointed at zero while doing so.
+fn turn(dial: &mut u32, instruction: Instruction) -> usize {
     let dial_size_u32: u32 = DIAL_SIZE.into();
     let dial_size_i32: i32 = DIAL_SIZE.into();
 
-    directions
-        .into_iter()
-        .map(|n| {
-            assert!(dial < dial_size_u32, "`dial` is out of bounds at {dial}");
-            assert_ne!(n, 0);
+    assert!(*dial < dial_size_u32, "`dial` is out of bounds at {dial}");
 
-            let mut new_d = i32::try_from(dial).expect("dial is invalid i32");
-            let old_d = new_d;
-            let mut crossings = 0;
+    let n = match instruction {
+        Instruction::Rotate(n) => n,
+        // Jumping directly to a position never crosses zero
+        Instruction::Set(n) => {
+            *dial = n;
+            return 0;
+        }
+    };
 
-            // Rotate the dial
-            new_d += n;
+    assert_ne!(n, 0);
 
-            // If we rotated it one or more full spins to the left
-            while new_d <= -dial_size_i32 {
-                new_d += dial_size_i32;
-                crossings += 1;
-            }
+    let old_d = i32::try_from(*dial).expect("dial is invalid i32");
 
-            // If we rotated it one or more full spins to the right
-            while new_d >= dial_size_i32 {
-                new_d -= dial_size_i32;
-                crossings += 1;
-            }
+    // Every full spin passes zero once. Counting them rather than
+    // spinning the dial keeps large amounts cheap and in range.
+    let full_spins = n.unsigned_abs() / dial_size_u32;
+    let rest = i32::try_from(n.unsigned_abs() % dial_size_u32).expect("rest is invalid i32");
+    let mut crossings = usize::try_from(full_spins).expect("Too many crossings");
 
-            // If we rotated left and crossed over zero
-            if let (1, -1) = (old_d.signum(), new_d.signum()) {
-                crossings += 1
-            }
+    // Rotate the dial the rest of the way
+    let mut new_d = old_d + rest * n.signum();
 
-            // If we rotated to the left and ended on zero
-            if let (-1, 0) = (n.signum(), new_d) {
-                crossings += 1;
+    // If we rotated it past zero to the right
+    if new_d >= dial_size_i32 {
+        new_d -= dial_size_i32;
+        crossings += 1;
+    }
+
+    // If we rotated left and crossed over zero
+    if let (1, -1) = (old_d.signum(), new_d.signum()) {
+        crossings += 1
+    }
+
+    // If we rotated to the left and ended on zero
+    if let (-1, 0) = (n.signum(), new_d) {
+        crossings += 1;
+    }
+
+    // Restore our state to 0..DIAL_SIZE
+    if new_d < 0 {
+        new_d += dial_size_i32;
+    }
+
+    // eprintln!("{old_d:3} {n:4} {new_d:3} {crossings:2}");
+    *dial = new_d.try_into().expect("dial is invalid u32");
+
+    crossings
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Instruction {
+    /// Turn the dial by this many clicks; negative is to the left.
+    Rotate(i32),
+    /// Point the dial directly at this number.
+    Set(u32),
+}
+
+impl From<i32> for Instruction {
+    fn from(n: i32) -> Self {
+        Instruction::Rotate(n)
+    }
+}
+
+/// Parses the instructions, panicking with the location of the first
+/// error.
+fn instructions(s: &str) -> impl Iterator<Item = Instruction> {
+    parse_instructions(s).map(|i| i.unwrap_or_else(|e| panic!("{e}")))
+}
+
+/// One instruction per line, in one of these forms:
+///
+/// - `L<n>` / `R<n>` rotates left / right by `n` clicks.
+/// - `S<n>` sets the dial to `n`.
+/// - Any of the above followed by `x<count>` (e.g. `R10x5`) repeats
+///   the instruction `count` times.
+///
+/// Blank lines are ignored, as is anything after a `#`.
+fn parse_instructions(s: &str) -> impl Iterator<Item = Result<Instruction, ParseError>> {
+    parse_lines(s, parse_instruction)
+}
+
+/// Skips blank lines and comments, handing the remainder of each line
+/// to `parse_line` and expanding any repeats. `parse_line` reports
+/// errors as a byte offset into the line it was given.
+fn parse_lines<T: Clone>(
+    s: &str,
+    parse_line: impl Fn(&str) -> Result<(T, usize), (usize, ParseErrorKind)>,
+) -> impl Iterator<Item = Result<T, ParseError>> {
+    s.lines()
+        .enumerate()
+        .filter_map(move |(line_idx, l)| {
+            let l = l.split_once('#').map_or(l, |(l, _comment)| l);
+            let trimmed = l.trim_start();
+            let column = l.len() - trimmed.len();
+            let trimmed = trimmed.trim_end();
+
+            (!trimmed.is_empty()).then(|| {
+                parse_line(trimmed).map_err(|(offset, kind)| ParseError {
+                    line: line_idx + 1,
+                    column: column + offset + 1,
+                    kind,
+                })
+            })
+        })
+        .flat_map(|r| {
+            let (value, count) = match r {
+                Ok((v, count)) => (Ok(v), count),
+                Err(e) => (Err(e), 1),
+            };
+            std::iter::repeat_n(value, count)
+        })
+}
+
+/// Parses a single non-empty instruction and its repeat count. Errors
+/// carry the byte offset of the problem within `l`.
+fn parse_instruction(l: &str) -> Result<(Instruction, usize), (usize, ParseErrorKind)> {
+    let mut chars = l.chars();
+    let kind = chars.next().expect("instruction is empty");
+    let rest = chars.as_str();
+    let amount_offset = kind.len_utf8();
+
+    let (amount, count) = match rest.split_once('x') {
+        Some((amount, count)) => (amount, Some(count)),
+        None => (rest, None),
+    };
+
+    if amount.is_empty() {
+        return Err((amount_offset, ParseErrorKind::MissingAmount));
+    }
+
+    let instruction = match kind {
+        'L' | 'R' => {
+            let n = parse_unsigned::<i32>(amount)
+                .ok_or((amount_offset, ParseErrorKind::InvalidAmount))?;
+            if n == 0 {
+                return Err((amount_offset, ParseErrorKind::ZeroRotation));
+            }
+            let direction = if kind == 'L' { -1 } else { 1 };
+            Instruction::Rotate(n * direction)
+        }
+        'S' => {
+            let n = parse_unsigned::<u32>(amount)
+                .ok_or((amount_offset, ParseErrorKind::InvalidAmount))?;
+            if n >= DIAL_SIZE.into() {
+                return Err((amount_offset, ParseErrorKind::SetOutOfRange(n)));
             }
+            Instruction::Set(n)
+        }
+        c => return Err((0, ParseErrorKind::UnknownInstruction(c))),
+    };
 
-            // Restore our state to 0..DIAL_SIZE
-            if new_d < 0 {
-                new_d += dial_size_i32;
+    let count = match count {
+        Some(count) => {
+            let count_offset = amount_offset + amount.len() + 1;
+            match parse_unsigned::<usize>(count) {
+                Some(0) | None => return Err((count_offset, ParseErrorKind::InvalidRepeat)),
+                Some(count) => count,
             }
+        }
+        None => 1,
+    };
 
-            // eprintln!("{old_d:3} {n:4} {new_d:3} {crossings:2}");
-            dial = new_d.try_into().expect("dial is invalid u32");
+    Ok((instruction, count))
+}
 
-            crossings
-        })
-        .sum()
+/// Only plain digits: `parse` would also accept a leading sign, which
+/// the direction already provides.
+fn parse_unsigned<T: std::str::FromStr>(s: &str) -> Option<T> {
+    if !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ParseError {
+    /// 1-based
+    line: usize,
+    /// 1-based, in bytes
+    column: usize,
+    kind: ParseErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseErrorKind {
+    UnknownInstruction(char),
+    MissingAmount,
+    InvalidAmount,
+    ZeroRotation,
+    SetOutOfRange(u32),
+    InvalidRepeat,
+    InvalidWheel,
 }
 
-fn spins(s: &str) -> impl Iterator<Item = i32> {
-    s.lines().map(|l| {
-        let (direction, n) = if let Some(n) = l.strip_prefix("L") {
-            (-1, n)
-        } else if let Some(n) = l.strip_prefix("R") {
-            (1, n)
-        } else {
-            panic!("Unknown direction");
-        };
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { line, column, kind } = self;
+        write!(f, "{line}:{column}: ")?;
 
-        let n = n.parse::<i32>().expect("Invalid amount");
-        n * direction
-    })
+        match kind {
+            ParseErrorKind::UnknownInstruction(c) => write!(f, "Unknown instruction `{c}`"),
+            ParseErrorKind::MissingAmount => write!(f, "Missing amount"),
+            ParseErrorKind::InvalidAmount => write!(f, "Invalid amount"),
+            ParseErrorKind::ZeroRotation => write!(f, "Rotation must not be zero"),
+            ParseErrorKind::SetOutOfRange(n) => {
+                write!(
+                    f,
+                    "Cannot set the dial to {n}, it only goes to {}",
+                    DIAL_SIZE - 1
+                )
+            }
+            ParseErrorKind::InvalidRepeat => write!(f, "Invalid repeat count"),
+            ParseErrorKind::InvalidWheel => write!(f, "Invalid wheel"),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -133,4 +315,105 @@ mod test {
         assert_eq!(2, password_0x434c49434b_core(50, [-150]));
         assert_eq!(2, password_0x434c49434b_core(50, [150]));
     }
+
+    #[test]
+    fn large_amounts() {
+        assert_eq!(21474836, password_0x434c49434b("R2147483647"));
+        assert_eq!(21474836, password_0x434c49434b("L2147483647"));
+        assert_eq!(21474836, password_0x434c49434b_core(50, [i32::MIN]));
+        assert_eq!(0, password("R2147483647"));
+    }
+
+    #[test]
+    fn turn_matches_spinning() {
+        // The original implementation, spinning the dial one full
+        // turn at a time.
+        fn spinning(dial: i32, n: i32) -> (usize, i32) {
+            let mut new_d = dial + n;
+            let mut crossings = 0;
+            while new_d <= -100 {
+                new_d += 100;
+                crossings += 1;
+            }
+            while new_d >= 100 {
+                new_d -= 100;
+                crossings += 1;
+            }
+            if let (1, -1) = (dial.signum(), new_d.signum()) {
+                crossings += 1
+            }
+            if let (-1, 0) = (n.signum(), new_d) {
+                crossings += 1;
+            }
+            (crossings, new_d.rem_euclid(100))
+        }
+
+        for dial in 0..100 {
+            for n in (-350..=350).filter(|&n| n != 0) {
+                let mut d = dial as u32;
+                let crossings = turn(&mut d, Instruction::Rotate(n));
+                assert_eq!(spinning(dial, n), (crossings, d as i32), "{dial} {n}");
+            }
+        }
+    }
+
+    #[test]
+    fn extended_syntax() {
+        let s = "# a comment\n\nL68 # trailing\n  R10x3\nS0\n";
+        let i = parse_instructions(s).collect::<Result<Vec<_>, _>>();
+
+        use Instruction::*;
+        assert_eq!(
+            Ok(vec![
+                Rotate(-68),
+                Rotate(10),
+                Rotate(10),
+                Rotate(10),
+                Set(0)
+            ]),
+            i,
+        );
+    }
+
+    #[test]
+    fn extended_syntax_counting() {
+        // Set never counts, even when it lands on zero
+        assert_eq!(0, password("S0"));
+        assert_eq!(1, password("S10\nL10"));
+        assert_eq!(0, password_0x434c49434b("S0\nS99"));
+
+        // Repeats count each stop...
+        assert_eq!(2, password("R25x4\nR50x2"));
+        // ...and each crossing
+        assert_eq!(2, password_0x434c49434b("R50x3"));
+        assert_eq!(2, password_0x434c49434b("S1\nL1x3\nR100"));
+    }
+
+    #[test]
+    fn extended_syntax_errors() {
+        fn first_error(s: &str) -> (usize, usize, ParseErrorKind) {
+            let e = parse_instructions(s)
+                .find_map(Result::err)
+                .expect("expected an error");
+            (e.line, e.column, e.kind)
+        }
+
+        use ParseErrorKind::*;
+        assert_eq!((1, 1, UnknownInstruction('U')), first_error("U5"));
+        assert_eq!((2, 3, UnknownInstruction('Q')), first_error("L1\n  Q5"));
+        assert_eq!((1, 2, MissingAmount), first_error("R"));
+        assert_eq!((1, 2, InvalidAmount), first_error("R1a"));
+        assert_eq!((1, 2, ZeroRotation), first_error("L0"));
+        assert_eq!((1, 2, SetOutOfRange(100)), first_error("S100"));
+        assert_eq!((1, 5, InvalidRepeat), first_error("R10x0"));
+        assert_eq!((3, 4, InvalidRepeat), first_error("\n\nL1x # comment"));
+
+        // Signs are not part of the amount
+        assert_eq!((1, 2, InvalidAmount), first_error("L-5"));
+        assert_eq!((1, 2, InvalidAmount), first_error("R-5"));
+        assert_eq!((1, 2, InvalidAmount), first_error("R+5"));
+        assert_eq!((1, 2, InvalidAmount), first_error("S+5"));
+        assert_eq!((1, 4, InvalidRepeat), first_error("R5x+2"));
+        assert_eq!((1, 4, InvalidRepeat), first_error("R5x-2"));
+    }
 }
@@ -0,0 +1,203 @@
+//! A combination lock built from a chain of day 1 dials. Whenever a
+//! wheel points at zero while turning, the next wheel is turned one
+//! click in the same direction, like the digits of an odometer.
+
+use crate::{
+    DIAL_SIZE, DIAL_START, Instruction, ParseErrorKind, parse_instruction, parse_lines,
+    parse_unsigned, turn,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Odometer {
+    wheels: Vec<Wheel>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Wheel {
+    dial: u32,
+    crossings: usize,
+}
+
+/// An instruction aimed at one wheel of an [`Odometer`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WheelInstruction {
+    pub wheel: usize,
+    pub instruction: Instruction,
+}
+
+impl Odometer {
+    /// Wheel 0 is the fastest-moving wheel, carrying into wheel 1,
+    /// and so on. Panics if a reading is not on the dial.
+    pub fn new(readings: impl IntoIterator<Item = u32>) -> Self {
+        let wheels = readings
+            .into_iter()
+            .enumerate()
+            .map(|(i, dial)| {
+                check_position(i, dial);
+                Wheel { dial, crossings: 0 }
+            })
+            .collect();
+        Self { wheels }
+    }
+
+    /// Every wheel starts at the same position as the day 1 dial.
+    pub fn with_wheels(n_wheels: usize) -> Self {
+        Self::new(std::iter::repeat_n(DIAL_START.into(), n_wheels))
+    }
+
+    /// Panics if the wheel does not exist or the instruction sets it
+    /// to a position that is not on the dial.
+    pub fn apply(&mut self, wheel: usize, instruction: Instruction) {
+        let n_wheels = self.wheels.len();
+        assert!(
+            wheel < n_wheels,
+            "wheel {wheel} does not exist, there are {n_wheels} wheels"
+        );
+        if let Instruction::Set(n) = instruction {
+            check_position(wheel, n);
+        }
+
+        let mut instruction = instruction;
+
+        for w in &mut self.wheels[wheel..] {
+            let direction = match instruction {
+                Instruction::Rotate(n) => n.signum(),
+                Instruction::Set(_) => 0,
+            };
+
+            let crossings = turn(&mut w.dial, instruction);
+            w.crossings += crossings;
+
+            if crossings == 0 || direction == 0 {
+                break;
+            }
+
+            let crossings = i32::try_from(crossings).expect("Too many crossings");
+            instruction = Instruction::Rotate(crossings * direction);
+        }
+    }
+
+    pub fn run(&mut self, instructions: impl IntoIterator<Item = WheelInstruction>) {
+        for WheelInstruction { wheel, instruction } in instructions {
+            self.apply(wheel, instruction);
+        }
+    }
+
+    /// The current position of every wheel
+    pub fn readings(&self) -> impl Iterator<Item = u32> {
+        self.wheels.iter().map(|w| w.dial)
+    }
+
+    /// How many times each wheel has pointed at zero, including turns
+    /// caused by a carry from the previous wheel. Crossings of the
+    /// last wheel have nowhere to carry to.
+    pub fn crossings(&self) -> impl Iterator<Item = usize> {
+        self.wheels.iter().map(|w| w.crossings)
+    }
+}
+
+fn check_position(wheel: usize, position: u32) {
+    assert!(
+        position < DIAL_SIZE.into(),
+        "position {position} for wheel {wheel} is not on the dial, which goes up to {}",
+        DIAL_SIZE - 1
+    );
+}
+
+/// Accepts the day 1 syntax, optionally prefixed with `<wheel>:` to
+/// target a wheel other than 0 (e.g. `2:R10x3`).
+pub fn wheel_instructions(s: &str, n_wheels: usize) -> impl Iterator<Item = WheelInstruction> {
+    parse_lines(s, move |l| {
+        let (wheel, instruction, offset) = match l.split_once(':') {
+            Some((wheel, instruction)) => {
+                let wheel = parse_unsigned::<usize>(wheel)
+                    .filter(|&w| w < n_wheels)
+                    .ok_or((0, ParseErrorKind::InvalidWheel))?;
+                (wheel, instruction, l.len() - instruction.len())
+            }
+            None => (0, l, 0),
+        };
+
+        if instruction.is_empty() {
+            return Err((offset, ParseErrorKind::MissingAmount));
+        }
+
+        let (instruction, count) =
+            parse_instruction(instruction).map_err(|(o, kind)| (offset + o, kind))?;
+
+        Ok((WheelInstruction { wheel, instruction }, count))
+    })
+    .map(|i| i.unwrap_or_else(|e| panic!("{e}")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(n_wheels: usize, s: &str) -> (Vec<u32>, Vec<usize>) {
+        let mut odometer = Odometer::with_wheels(n_wheels);
+        odometer.run(wheel_instructions(s, n_wheels));
+        (
+            odometer.readings().collect(),
+            odometer.crossings().collect(),
+        )
+    }
+
+    #[test]
+    fn single_wheel_matches_part2() {
+        const EXAMPLE: &str = include_str!("../example.txt");
+
+        let (_, crossings) = run(1, EXAMPLE);
+        assert_eq!(vec![crate::password_0x434c49434b(EXAMPLE)], crossings);
+    }
+
+    #[test]
+    fn carries() {
+        assert_eq!((vec![0, 51, 50], vec![1, 0, 0]), run(3, "R50"));
+        assert_eq!((vec![50, 52, 50], vec![2, 0, 0]), run(3, "R200"));
+        assert_eq!((vec![99, 49, 50], vec![1, 0, 0]), run(3, "L51"));
+    }
+
+    #[test]
+    fn carries_cascade() {
+        assert_eq!((vec![0, 0, 51], vec![1, 1, 0]), run(3, "1:S99\nR50"));
+        assert_eq!((vec![0, 0, 0], vec![1, 1, 1]), run(3, "1:S99\n2:S99\nR50"));
+    }
+
+    #[test]
+    fn targeted_wheel() {
+        assert_eq!((vec![50, 70, 51], vec![0, 1, 0]), run(3, "1:R25x2\n1:L30"));
+        assert_eq!((vec![50, 80, 49], vec![0, 1, 0]), run(3, "1:L70"));
+        assert_eq!((vec![50, 50, 10], vec![0, 0, 0]), run(3, "2:S10"));
+    }
+
+    #[test]
+    #[should_panic(expected = "1:1: Invalid wheel")]
+    fn targeted_wheel_out_of_range() {
+        run(2, "2:R1");
+    }
+
+    #[test]
+    #[should_panic(expected = "wheel 2 does not exist, there are 2 wheels")]
+    fn apply_to_missing_wheel() {
+        Odometer::with_wheels(2).apply(2, Instruction::Rotate(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "wheel 5 does not exist, there are 2 wheels")]
+    fn apply_past_missing_wheel() {
+        Odometer::with_wheels(2).apply(5, Instruction::Rotate(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "position 100 for wheel 1 is not on the dial, which goes up to 99")]
+    fn set_off_the_dial() {
+        Odometer::with_wheels(2).apply(1, Instruction::Set(100));
+    }
+
+    #[test]
+    #[should_panic(expected = "position 250 for wheel 2 is not on the dial, which goes up to 99")]
+    fn reading_off_the_dial() {
+        Odometer::new([0, 99, 250]);
+    }
+}
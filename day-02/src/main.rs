@@ -1,97 +1,233 @@
 #![feature(int_format_into)]
 
-use core::fmt::NumBuffer;
+use rules::{AtLeastRepeats, Solver};
+use std::ops;
+
+// Only some of the rules are needed for the puzzle answers
+#[cfg_attr(not(test), expect(dead_code))]
+mod rules;
 
 const INPUT: &str = include_str!("../input.txt");
 
+// The base the input IDs are written in. The answers are printed in
+// the same base.
+const RADIX: Radix = DECIMAL;
+
+// Print any input ranges that overlap. Overlaps are merged before
+// solving, so this is only informational.
+const REPORT_OVERLAPS: bool = false;
+
 fn main() {
-    let part1 = sum_of_invalid_ids(INPUT);
+    if REPORT_OVERLAPS {
+        for o in overlapping_id_ranges(INPUT, RADIX) {
+            eprintln!(
+                "Ranges {} and {} share {} IDs ({:?})",
+                o.ranges[0] + 1,
+                o.ranges[1] + 1,
+                o.size(),
+                o.shared,
+            );
+        }
+    }
+
+    let part1 = sum_of_invalid_ids_in_radix(INPUT, RADIX);
     assert_eq!(12586854255, part1);
-    println!("{part1}");
+    println!("{}", format_id(part1, RADIX));
 
-    let part2 = sum_of_all_invalid_ids(INPUT);
+    let part2 = sum_of_all_invalid_ids_in_radix(INPUT, RADIX);
     assert_eq!(17298174201, part2);
-    println!("{part2}");
+    println!("{}", format_id(part2, RADIX));
 }
 
-fn sum_of_invalid_ids(s: &str) -> u64 {
-    id_ranges(s)
-        .map(|(s, e)| s..=e)
-        // Find the possible bounds of paired upper / lower numbers
-        .filter_map(|orig_range| {
-            let s = orig_range.clone().filter_map(upper).next();
-            let e = orig_range.clone().rev().filter_map(upper).next();
+type Id = u128;
+type IdRange = ops::RangeInclusive<Id>;
 
-            Some((orig_range, s?..=e?))
-        })
-        // Find all possible upper / lower pairs
-        .flat_map(|(orig_range, search_range)| {
-            search_range.flat_map(move |n| {
-                let n_digits = n_digits(n);
-                let k = 10u64.pow(n_digits);
-                let v = n * k + n;
-
-                // Check we are still in-bounds of the original range
-                orig_range.contains(&v).then_some(v)
-            })
-        })
-        .sum()
+/// Any base from 2 to 36, using the digits `0-9` and then `a-z`.
+type Radix = u32;
+
+const DECIMAL: Radix = 10;
+
+#[cfg_attr(not(test), expect(dead_code))]
+fn sum_of_invalid_ids(s: &str) -> Id {
+    sum_of_invalid_ids_in_radix(s, DECIMAL)
 }
 
-fn sum_of_all_invalid_ids(s: &str) -> u64 {
-    id_ranges(s)
-        .flat_map(|(s, e)| {
-            (s..=e).flat_map(|n| {
-                let mut buf = NumBuffer::new();
-                let buf = n.format_into(&mut buf);
-                let buf = buf.as_bytes();
+fn sum_of_invalid_ids_in_radix(s: &str, radix: Radix) -> Id {
+    normalized_id_ranges(s, radix)
+        .into_iter()
+        .flat_map(|range| split_by_n_digits(range, radix))
+        .filter_map(|(n_digits, range)| {
+            let upper = |i| upper(i, radix);
+            let s = upper(*range.start())?;
+            let e = upper(*range.end())?;
 
-                let mut sequence_lengths = 1..buf.len();
-                let any_sequence_repeated = sequence_lengths.any(|l| all_chunks_same(buf, l));
+            // Pairing a half with itself is `half * k`
+            let k = Id::from(radix).pow(n_digits / 2) + 1;
 
-                any_sequence_repeated.then_some(n)
-            })
+            // Find the bounds of the halves whose pair is still
+            // in-bounds of the original range
+            let s = if s * k < *range.start() { s + 1 } else { s };
+            let e = if e * k > *range.end() { e - 1 } else { e };
+
+            Some(sum_of_range(s, e) * k)
         })
         .sum()
 }
 
-fn id_ranges(s: &str) -> impl Iterator<Item = (u64, u64)> {
-    s.split(",").map(|p| {
+#[cfg_attr(not(test), expect(dead_code))]
+fn sum_of_all_invalid_ids(s: &str) -> Id {
+    sum_of_all_invalid_ids_in_radix(s, DECIMAL)
+}
+
+fn sum_of_all_invalid_ids_in_radix(s: &str, radix: Radix) -> Id {
+    Solver::with_radix(s, radix, AtLeastRepeats::new(2)).sum()
+}
+
+/// Splits a range into pieces where every ID has the same number of
+/// digits.
+fn split_by_n_digits(range: IdRange, radix: Radix) -> impl Iterator<Item = (u32, IdRange)> {
+    let start = *range.start();
+    let end = *range.end();
+    let base = Id::from(radix);
+
+    (n_digits(start, radix)..=n_digits(end, radix)).filter_map(move |n_digits| {
+        let lower = if n_digits == 1 {
+            0
+        } else {
+            base.pow(n_digits - 1)
+        };
+        let upper = base.checked_pow(n_digits).map_or(Id::MAX, |u| u - 1);
+
+        let s = Id::max(start, lower);
+        let e = Id::min(end, upper);
+        (s <= e).then_some((n_digits, s..=e))
+    })
+}
+
+/// Gauss' sum of `s..=e`, dividing before multiplying to avoid
+/// overflow.
+fn sum_of_range(s: Id, e: Id) -> Id {
+    if s > e {
+        return 0;
+    }
+
+    let n = e - s + 1;
+    if n.is_multiple_of(2) {
+        (n / 2) * (s + e)
+    } else {
+        n * ((s + e) / 2)
+    }
+}
+
+fn divisors(n: u32) -> impl Iterator<Item = u32> {
+    (1..=n).filter(move |d| n.is_multiple_of(*d))
+}
+
+fn id_ranges(s: &str, radix: Radix) -> impl Iterator<Item = (Id, Id)> {
+    assert!((2..=36).contains(&radix), "radix {radix} is not supported");
+
+    s.split(",").map(move |p| {
         let (s, e) = p.trim().split_once("-").expect("pair malformed");
-        let [s, e] = [s, e].map(|i| i.parse::<u64>().expect("id malformed"));
+        let [s, e] = [s, e].map(|i| Id::from_str_radix(i, radix).expect("id malformed"));
+        assert!(s <= e, "range {p} is reversed");
         (s, e)
     })
 }
 
-fn n_digits(i: u64) -> u32 {
-    i.ilog10() + 1
+/// Sorts the ranges and merges any that overlap or touch so that
+/// each ID is only present once.
+fn normalized_id_ranges(s: &str, radix: Radix) -> Vec<IdRange> {
+    let mut ranges = id_ranges(s, radix).collect::<Vec<_>>();
+    ranges.sort();
+
+    let mut normalized = Vec::<IdRange>::with_capacity(ranges.len());
+
+    for (s, e) in ranges {
+        match normalized.last_mut() {
+            Some(prev) if s <= prev.end().saturating_add(1) => {
+                *prev = *prev.start()..=Id::max(*prev.end(), e);
+            }
+            _ => normalized.push(s..=e),
+        }
+    }
+
+    normalized
 }
 
-fn upper(i: u64) -> Option<u64> {
-    let n_digits = n_digits(i);
-    n_digits.is_multiple_of(2).then(|| {
-        let k = 10u64.pow(n_digits / 2);
-        i / k
-    })
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Overlap {
+    /// Zero-based positions in the input, in ascending order
+    ranges: [usize; 2],
+    shared: IdRange,
+}
+
+impl Overlap {
+    fn size(&self) -> Id {
+        self.shared.end() - self.shared.start() + 1
+    }
 }
 
-fn all_chunks_same<T: Eq>(buf: &[T], n: usize) -> bool {
-    let mut chunks = buf.chunks_exact(n);
+/// Every pair of input ranges that have IDs in common.
+fn overlapping_id_ranges(s: &str, radix: Radix) -> Vec<Overlap> {
+    let mut ranges = id_ranges(s, radix).enumerate().collect::<Vec<_>>();
+    ranges.sort_by_key(|&(_, r)| r);
 
-    if !chunks.remainder().is_empty() {
-        return false;
+    let mut overlaps = Vec::new();
+
+    for (i, &(a_idx, (_, a_e))) in ranges.iter().enumerate() {
+        // Sorted by start, so stop at the first range that begins
+        // after this one ends.
+        for &(b_idx, (b_s, b_e)) in &ranges[i + 1..] {
+            if b_s > a_e {
+                break;
+            }
+
+            let mut idxs = [a_idx, b_idx];
+            idxs.sort();
+
+            overlaps.push(Overlap {
+                ranges: idxs,
+                shared: b_s..=Id::min(a_e, b_e),
+            });
+        }
+    }
+
+    overlaps.sort_by_key(|o| o.ranges);
+    overlaps
+}
+
+fn format_id(mut i: Id, radix: Radix) -> String {
+    let mut digits = Vec::new();
+
+    loop {
+        let d = u32::try_from(i % Id::from(radix)).expect("digit out of range");
+        digits.push(char::from_digit(d, radix).expect("invalid radix"));
+        i /= Id::from(radix);
+
+        if i == 0 {
+            break;
+        }
     }
 
-    let Some(head) = chunks.next() else {
-        return false;
-    };
+    digits.into_iter().rev().collect()
+}
 
-    chunks.all(|c| head == c)
+fn n_digits(i: Id, radix: Radix) -> u32 {
+    i.checked_ilog(radix.into()).map_or(1, |l| l + 1)
+}
+
+fn upper(i: Id, radix: Radix) -> Option<Id> {
+    let n_digits = n_digits(i, radix);
+    n_digits.is_multiple_of(2).then(|| {
+        let k = Id::from(radix).pow(n_digits / 2);
+        i / k
+    })
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use core::fmt::NumBuffer;
 
     const EXAMPLE: &str = include_str!("../example.txt");
 
@@ -100,20 +236,44 @@ mod test {
         assert_eq!(1227775554, sum_of_invalid_ids(EXAMPLE));
     }
 
+    #[test]
+    fn part1_wide_ranges() {
+        assert_eq!(495495949990950, sum_of_invalid_ids("1-99999999999"));
+        assert_eq!(0, sum_of_invalid_ids("100-999,10000-99999"));
+        assert_eq!(1010, sum_of_invalid_ids("1000-1010"));
+        assert_eq!(9999, sum_of_invalid_ids("9999-10000"));
+    }
+
+    #[test]
+    fn part1_matches_brute_force() {
+        for (s, e) in [(1, 10_000), (95, 115), (998, 1012), (123_456, 1_234_567)] {
+            let input = format!("{s}-{e}");
+            let expected = (s..=e)
+                .filter(|&n| {
+                    let n = n.to_string().into_bytes();
+                    n.len().is_multiple_of(2) && all_chunks_same(&n, n.len() / 2)
+                })
+                .sum::<Id>();
+
+            assert_eq!(expected, sum_of_invalid_ids(&input), "{input}");
+        }
+    }
+
     #[test]
     fn n_digits_exercise() {
-        assert_eq!(1, n_digits(1));
-        assert_eq!(2, n_digits(12));
-        assert_eq!(3, n_digits(123));
-        assert_eq!(4, n_digits(1234));
+        assert_eq!(1, n_digits(0, DECIMAL));
+        assert_eq!(1, n_digits(1, DECIMAL));
+        assert_eq!(2, n_digits(12, DECIMAL));
+        assert_eq!(3, n_digits(123, DECIMAL));
+        assert_eq!(4, n_digits(1234, DECIMAL));
     }
 
     #[test]
     fn upper_exercise() {
-        assert_eq!(None, upper(1));
-        assert_eq!(Some(1), upper(12));
-        assert_eq!(None, upper(123));
-        assert_eq!(Some(12), upper(1234));
+        assert_eq!(None, upper(1, DECIMAL));
+        assert_eq!(Some(1), upper(12, DECIMAL));
+        assert_eq!(None, upper(123, DECIMAL));
+        assert_eq!(Some(12), upper(1234, DECIMAL));
     }
 
     #[test]
@@ -121,6 +281,181 @@ mod test {
         assert_eq!(4174379265, sum_of_all_invalid_ids(EXAMPLE));
     }
 
+    #[test]
+    fn part2_matches_brute_force() {
+        for (s, e) in [(1, 10_000), (95, 115), (998, 1012), (123_456, 1_234_567)] {
+            let input = format!("{s}-{e}");
+            assert_eq!(
+                sum_of_all_invalid_ids_brute_force(s..=e),
+                sum_of_all_invalid_ids(&input),
+                "{input}",
+            );
+        }
+    }
+
+    #[test]
+    fn part2_wide_ranges() {
+        // 11 + 22 + ... + 99
+        assert_eq!(495, sum_of_all_invalid_ids("0-99"));
+        // 37 digits: every ID made of a single digit
+        let n = "1".repeat(37).parse::<Id>().unwrap();
+        assert_eq!(n * 45, sum_of_all_invalid_ids(&format!("{n}-{}", n * 9)));
+    }
+
+    #[test]
+    fn longest_ids() {
+        // 39 digits, the most a decimal ID can have
+        let start = Id::from(10u8).pow(38);
+        assert_eq!(0, sum_of_all_invalid_ids(&format!("{start}-{}", start + 5)));
+        let n = "1".repeat(39).parse::<Id>().unwrap();
+        assert_eq!(n, sum_of_all_invalid_ids(&format!("{n}-{}", n + 5)));
+
+        // 128 binary digits. Only the last ID is a repeated block.
+        let binary = |s: Id, e: Id| format!("{}-{}", format_id(s, 2), format_id(e, 2));
+        assert_eq!(
+            0,
+            sum_of_all_invalid_ids_in_radix(&binary(1 << 127, (1 << 127) + 5), 2)
+        );
+        assert_eq!(
+            Id::MAX,
+            sum_of_all_invalid_ids_in_radix(&binary(Id::MAX - 5, Id::MAX), 2)
+        );
+        assert_eq!(
+            Id::MAX,
+            sum_of_invalid_ids_in_radix(&binary(Id::MAX - 5, Id::MAX), 2)
+        );
+    }
+
+    #[test]
+    fn overlapping_ranges_count_once() {
+        assert_eq!(11 + 22, sum_of_invalid_ids("10-30,20-25,1-22"));
+        assert_eq!(11 + 22, sum_of_all_invalid_ids("10-30,20-25,1-22"));
+        assert_eq!(111, sum_of_all_invalid_ids("100-110,111-111,112-120"));
+    }
+
+    #[test]
+    fn normalized_id_ranges_exercise() {
+        assert_eq!(
+            vec![1..=30, 40..=50],
+            normalized_id_ranges("40-50,10-30,20-25,1-22", DECIMAL)
+        );
+        assert_eq!(vec![1..=9], normalized_id_ranges("1-3,4-6,7-9", DECIMAL));
+        assert_eq!(vec![5..=5], normalized_id_ranges("5-5,5-5", DECIMAL));
+    }
+
+    #[test]
+    #[should_panic(expected = "range 20-10 is reversed")]
+    fn reversed_range() {
+        sum_of_invalid_ids("1-5,20-10");
+    }
+
+    #[test]
+    fn overlapping_id_ranges_exercise() {
+        let overlaps = overlapping_id_ranges("10-30,40-50,20-25,1-22,30-35", DECIMAL);
+
+        assert_eq!(
+            vec![
+                Overlap {
+                    ranges: [0, 2],
+                    shared: 20..=25
+                },
+                Overlap {
+                    ranges: [0, 3],
+                    shared: 10..=22
+                },
+                Overlap {
+                    ranges: [0, 4],
+                    shared: 30..=30
+                },
+                Overlap {
+                    ranges: [2, 3],
+                    shared: 20..=22
+                },
+            ],
+            overlaps,
+        );
+        assert_eq!(
+            vec![6, 13, 1, 3],
+            overlaps.iter().map(Overlap::size).collect::<Vec<_>>()
+        );
+
+        assert!(overlapping_id_ranges(include_str!("../example.txt"), DECIMAL).is_empty());
+    }
+
+    #[test]
+    fn other_radixes() {
+        // 1010 - 1111 in binary: 10 (0b1010) and 15 (0b1111)
+        assert_eq!(25, sum_of_invalid_ids_in_radix("1000-1111", 2));
+        assert_eq!(25, sum_of_all_invalid_ids_in_radix("1000-1111", 2));
+
+        // 0xaa, 0xbb, 0xcc
+        assert_eq!(0xaa + 0xbb + 0xcc, sum_of_invalid_ids_in_radix("a0-cf", 16));
+        assert_eq!(
+            0xaa + 0xbb + 0xcc,
+            sum_of_all_invalid_ids_in_radix("a0-cf", 16)
+        );
+
+        // Base 36 uses every letter
+        assert_eq!(36 * 35 + 35, sum_of_invalid_ids_in_radix("zz-zz", 36));
+    }
+
+    #[test]
+    fn other_radixes_match_brute_force() {
+        for radix in [2, 3, 7, 16, 36] {
+            let (s, e) = (0, 5_000);
+            let input = format!("{}-{}", format_id(s, radix), format_id(e, radix));
+            let expected = (s..=e)
+                .filter(|&n| {
+                    let digits = format_id(n, radix).into_bytes();
+                    (1..digits.len()).any(|l| all_chunks_same(&digits, l))
+                })
+                .sum::<Id>();
+
+            assert_eq!(
+                expected,
+                sum_of_all_invalid_ids_in_radix(&input, radix),
+                "{radix}"
+            );
+        }
+    }
+
+    #[test]
+    fn format_id_exercise() {
+        assert_eq!("0", format_id(0, 2));
+        assert_eq!("1010", format_id(10, 2));
+        assert_eq!("ff", format_id(255, 16));
+        assert_eq!("zz", format_id(36 * 36 - 1, 36));
+        assert_eq!("1234", format_id(1234, DECIMAL));
+    }
+
+    /// The original implementation, checking every ID in the range
+    fn sum_of_all_invalid_ids_brute_force(range: IdRange) -> Id {
+        range
+            .filter(|&n| {
+                let mut buf = NumBuffer::new();
+                let buf = n.format_into(&mut buf);
+                let buf = buf.as_bytes();
+
+                let mut sequence_lengths = 1..buf.len();
+                sequence_lengths.any(|l| all_chunks_same(buf, l))
+            })
+            .sum()
+    }
+
+    fn all_chunks_same<T: Eq>(buf: &[T], n: usize) -> bool {
+        let mut chunks = buf.chunks_exact(n);
+
+        if !chunks.remainder().is_empty() {
+            return false;
+        }
+
+        let Some(head) = chunks.next() else {
+            return false;
+        };
+
+        chunks.all(|c| head == c)
+    }
+
     #[test]
     fn all_chunks_same_exercise() {
         assert!(all_chunks_same(b"12341234", 4));
@@ -0,0 +1,457 @@
+//! Different ways of deciding that an ID is invalid.
+
+use itertools::Itertools;
+use std::{collections::BTreeMap, iter, ops};
+
+use crate::{
+    DECIMAL, Id, IdRange, Radix, divisors, n_digits, normalized_id_ranges, split_by_n_digits,
+    sum_of_range,
+};
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Tally {
+    pub sum: Id,
+    pub count: Id,
+}
+
+impl Tally {
+    fn single(id: Id) -> Self {
+        Self { sum: id, count: 1 }
+    }
+}
+
+impl ops::Add for Tally {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            sum: self.sum + other.sum,
+            count: self.count + other.count,
+        }
+    }
+}
+
+impl ops::Sub for Tally {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            sum: self.sum - other.sum,
+            count: self.count - other.count,
+        }
+    }
+}
+
+impl iter::Sum for Tally {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), ops::Add::add)
+    }
+}
+
+/// Rules look at the digits of an ID written in a given radix.
+pub trait IdRule {
+    fn matches(&self, id: Id, radix: Radix) -> bool;
+
+    /// The matching IDs in `range`, in ascending order. Every ID in
+    /// `range` has `n_digits` digits.
+    ///
+    /// By default, this checks every ID in the range.
+    fn ids(&self, radix: Radix, _n_digits: u32, range: IdRange) -> impl Iterator<Item = Id> {
+        range.filter(move |&id| self.matches(id, radix))
+    }
+
+    /// The sum and count of the matching IDs in `range`. Every ID in
+    /// `range` has `n_digits` digits.
+    fn tally(&self, radix: Radix, n_digits: u32, range: &IdRange) -> Tally {
+        self.ids(radix, n_digits, range.clone())
+            .map(Tally::single)
+            .sum()
+    }
+}
+
+/// A block of digits repeated exactly this many times, such as
+/// `123123` for 2.
+#[derive(Debug, Copy, Clone)]
+pub struct ExactRepeats(u32);
+
+impl ExactRepeats {
+    /// Panics if `n_repeats` is less than 2, as every ID is a single
+    /// repeat of itself.
+    pub fn new(n_repeats: u32) -> Self {
+        check_repeats(n_repeats);
+        Self(n_repeats)
+    }
+}
+
+impl IdRule for ExactRepeats {
+    fn matches(&self, id: Id, radix: Radix) -> bool {
+        let n_digits = n_digits(id, radix);
+        n_digits.is_multiple_of(self.0)
+            && id.is_multiple_of(multiplier(radix, n_digits, n_digits / self.0))
+    }
+
+    fn ids(&self, radix: Radix, n_digits: u32, range: IdRange) -> impl Iterator<Item = Id> {
+        let blocks = n_digits
+            .is_multiple_of(self.0)
+            .then(|| repeated_blocks(&range, radix, n_digits, n_digits / self.0));
+
+        blocks.into_iter().flatten()
+    }
+
+    fn tally(&self, radix: Radix, n_digits: u32, range: &IdRange) -> Tally {
+        if n_digits.is_multiple_of(self.0) {
+            repeated_block_tally(range, radix, n_digits, n_digits / self.0)
+        } else {
+            Tally::default()
+        }
+    }
+}
+
+/// A block of digits repeated at least this many times, such as
+/// `123123` or `121212` for 2.
+#[derive(Debug, Copy, Clone)]
+pub struct AtLeastRepeats(u32);
+
+impl AtLeastRepeats {
+    /// Panics if `n_repeats` is less than 2, as every ID is a single
+    /// repeat of itself.
+    pub fn new(n_repeats: u32) -> Self {
+        check_repeats(n_repeats);
+        Self(n_repeats)
+    }
+
+    fn block_lens(&self, n_digits: u32) -> impl Iterator<Item = u32> {
+        divisors(n_digits).filter(move |&l| n_digits / l >= self.0)
+    }
+}
+
+fn check_repeats(n_repeats: u32) {
+    assert!(
+        n_repeats >= 2,
+        "IDs need at least 2 repeats to be invalid, not {n_repeats}"
+    );
+}
+
+impl IdRule for AtLeastRepeats {
+    fn matches(&self, id: Id, radix: Radix) -> bool {
+        let n_digits = n_digits(id, radix);
+        self.block_lens(n_digits)
+            .any(|l| id.is_multiple_of(multiplier(radix, n_digits, l)))
+    }
+
+    fn ids(&self, radix: Radix, n_digits: u32, range: IdRange) -> impl Iterator<Item = Id> {
+        self.block_lens(n_digits)
+            .map(|l| repeated_blocks(&range, radix, n_digits, l))
+            .kmerge()
+            .dedup()
+    }
+
+    fn tally(&self, radix: Radix, n_digits: u32, range: &IdRange) -> Tally {
+        // An ID made of a repeated block of length `l` is also made
+        // of a repeated block of any multiple of `l` (that divides
+        // `n_digits`). Attribute each ID only to its shortest block
+        // so that it's counted exactly once.
+        //
+        // Any divisor of an allowed block length is also allowed.
+        let mut shortest_block_tallies = BTreeMap::new();
+
+        for block_len in self.block_lens(n_digits) {
+            let shorter_blocks: Tally = divisors(block_len)
+                .filter(|&l| l != block_len)
+                .map(|l| shortest_block_tallies[&l])
+                .sum();
+            let tally = repeated_block_tally(range, radix, n_digits, block_len) - shorter_blocks;
+            shortest_block_tallies.insert(block_len, tally);
+        }
+
+        shortest_block_tallies.into_values().sum()
+    }
+}
+
+/// The same digits forwards and backwards, such as `12321`.
+#[derive(Debug, Copy, Clone)]
+pub struct Palindrome;
+
+impl IdRule for Palindrome {
+    fn matches(&self, id: Id, radix: Radix) -> bool {
+        let n_digits = n_digits(id, radix);
+        let k = Id::from(radix).pow(n_digits / 2);
+        id == mirror(id / k, radix, n_digits)
+    }
+
+    /// Builds each palindrome from its first half
+    fn ids(&self, radix: Radix, n_digits: u32, range: IdRange) -> impl Iterator<Item = Id> {
+        let k = Id::from(radix).pow(n_digits / 2);
+        let first_halves = range.start() / k..=range.end() / k;
+
+        first_halves
+            .map(move |h| mirror(h, radix, n_digits))
+            .filter(move |id| range.contains(id))
+    }
+}
+
+/// Builds the `n_digits` long palindrome starting with `first_half`.
+fn mirror(first_half: Id, radix: Radix, n_digits: u32) -> Id {
+    let base = Id::from(radix);
+    let k = base.pow(n_digits / 2);
+    // The middle digit of an odd length palindrome isn't repeated
+    let mirrored = first_half / base.pow(n_digits % 2);
+    let reversed = digits(mirrored, radix).fold(0, |acc, d| acc * base + Id::from(d));
+
+    first_half * k + reversed
+}
+
+/// The sum of the digits passes a test, such as "is a multiple of 7".
+#[derive(Debug, Copy, Clone)]
+pub struct DigitSum<F>(pub F);
+
+impl<F> IdRule for DigitSum<F>
+where
+    F: Fn(u32) -> bool,
+{
+    fn matches(&self, id: Id, radix: Radix) -> bool {
+        (self.0)(digits(id, radix).sum())
+    }
+}
+
+/// From least to most significant
+fn digits(mut id: Id, radix: Radix) -> impl Iterator<Item = u32> {
+    let base = Id::from(radix);
+    iter::from_fn(move || {
+        (id != 0).then(|| {
+            let d = id % base;
+            id /= base;
+            d as u32
+        })
+    })
+}
+
+/// Turns a block of `block_len` digits into an ID of `n_digits`
+/// digits made of that block repeated. This is a number like
+/// `1010101`.
+///
+/// This is built one block at a time, as `radix^n_digits` doesn't fit
+/// in an `Id` for the longest IDs.
+fn multiplier(radix: Radix, n_digits: u32, block_len: u32) -> Id {
+    let block = Id::from(radix).pow(block_len);
+    (1..n_digits / block_len).fold(1, |m, _| m * block + 1)
+}
+
+/// The blocks of `block_len` digits that, when repeated to fill
+/// `n_digits`, lie inside `range`.
+fn repeated_block_bounds(range: &IdRange, radix: Radix, n_digits: u32, block_len: u32) -> IdRange {
+    let base = Id::from(radix);
+    let multiplier = multiplier(radix, n_digits, block_len);
+
+    let smallest_block = base.pow(block_len - 1);
+    let largest_block = base.pow(block_len) - 1;
+
+    let s = Id::max(range.start().div_ceil(multiplier), smallest_block);
+    let e = Id::min(range.end() / multiplier, largest_block);
+
+    s..=e
+}
+
+fn repeated_blocks(
+    range: &IdRange,
+    radix: Radix,
+    n_digits: u32,
+    block_len: u32,
+) -> impl Iterator<Item = Id> + use<> {
+    let multiplier = multiplier(radix, n_digits, block_len);
+    repeated_block_bounds(range, radix, n_digits, block_len).map(move |b| b * multiplier)
+}
+
+/// All IDs in `range` made from a block of `block_len` digits
+/// repeated to fill `n_digits`. All IDs in `range` must have
+/// `n_digits` digits.
+fn repeated_block_tally(range: &IdRange, radix: Radix, n_digits: u32, block_len: u32) -> Tally {
+    let blocks = repeated_block_bounds(range, radix, n_digits, block_len);
+    let (s, e) = blocks.into_inner();
+
+    if s > e {
+        return Tally::default();
+    }
+
+    Tally {
+        sum: sum_of_range(s, e) * multiplier(radix, n_digits, block_len),
+        count: e - s + 1,
+    }
+}
+
+/// Applies a rule to every range of IDs from the input.
+pub struct Solver<R> {
+    ranges: Vec<IdRange>,
+    radix: Radix,
+    rule: R,
+}
+
+impl<R: IdRule> Solver<R> {
+    pub fn new(s: &str, rule: R) -> Self {
+        Self::with_radix(s, DECIMAL, rule)
+    }
+
+    /// The IDs in `s` are written in `radix` and the rule is applied
+    /// to their digits in that radix.
+    pub fn with_radix(s: &str, radix: Radix, rule: R) -> Self {
+        let ranges = normalized_id_ranges(s, radix);
+        Self {
+            ranges,
+            radix,
+            rule,
+        }
+    }
+
+    pub fn tally(&self) -> Tally {
+        self.ranges
+            .iter()
+            .cloned()
+            .flat_map(|range| split_by_n_digits(range, self.radix))
+            .map(|(n_digits, range)| self.rule.tally(self.radix, n_digits, &range))
+            .sum()
+    }
+
+    pub fn sum(&self) -> Id {
+        self.tally().sum
+    }
+
+    pub fn count(&self) -> Id {
+        self.tally().count
+    }
+
+    /// Each input range (after overlapping ranges have been merged)
+    /// along with the matching IDs inside it, in ascending order.
+    pub fn ids(&self) -> impl Iterator<Item = (IdRange, impl Iterator<Item = Id>)> {
+        self.ranges.iter().map(|range| {
+            let ids = split_by_n_digits(range.clone(), self.radix)
+                .flat_map(|(n_digits, range)| self.rule.ids(self.radix, n_digits, range));
+            (range.clone(), ids)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("../example.txt");
+
+    fn ids<R: IdRule>(s: &str, rule: R) -> Vec<Id> {
+        Solver::new(s, rule)
+            .ids()
+            .flat_map(|(_, ids)| ids)
+            .collect()
+    }
+
+    fn check_against_matches<R: IdRule>(rule: R) {
+        for (s, e) in [(0, 10_000), (95, 115), (998, 1012), (123_456, 234_567)] {
+            let input = format!("{s}-{e}");
+            let expected = (s..=e)
+                .filter(|&id| rule.matches(id, DECIMAL))
+                .collect::<Vec<_>>();
+
+            let solver = Solver::new(&input, &rule);
+            assert_eq!(expected, ids(&input, &rule), "{input}");
+            assert_eq!(expected.iter().sum::<Id>(), solver.sum(), "{input}");
+            assert_eq!(expected.len() as Id, solver.count(), "{input}");
+        }
+    }
+
+    impl<R: IdRule> IdRule for &R {
+        fn matches(&self, id: Id, radix: Radix) -> bool {
+            (**self).matches(id, radix)
+        }
+
+        fn ids(&self, radix: Radix, n_digits: u32, range: IdRange) -> impl Iterator<Item = Id> {
+            (**self).ids(radix, n_digits, range)
+        }
+
+        fn tally(&self, radix: Radix, n_digits: u32, range: &IdRange) -> Tally {
+            (**self).tally(radix, n_digits, range)
+        }
+    }
+
+    #[test]
+    fn exact_repeats() {
+        assert_eq!(
+            crate::sum_of_invalid_ids(EXAMPLE),
+            Solver::new(EXAMPLE, ExactRepeats::new(2)).sum()
+        );
+        assert_eq!(vec![111, 222, 333], ids("100-399", ExactRepeats::new(3)));
+        assert_eq!(
+            vec![1010, 1111, 1212],
+            ids("1000-1300", ExactRepeats::new(2))
+        );
+        check_against_matches(ExactRepeats::new(2));
+        check_against_matches(ExactRepeats::new(3));
+    }
+
+    #[test]
+    fn at_least_repeats() {
+        let solver = Solver::new(EXAMPLE, AtLeastRepeats::new(2));
+        assert_eq!(4174379265, solver.sum());
+        assert_eq!(13, solver.count());
+        assert_eq!(
+            vec![1010, 1111, 1212],
+            ids("1000-1300", AtLeastRepeats::new(2))
+        );
+        assert_eq!(vec![1111], ids("1000-1300", AtLeastRepeats::new(3)));
+        check_against_matches(AtLeastRepeats::new(2));
+        check_against_matches(AtLeastRepeats::new(3));
+    }
+
+    #[test]
+    #[should_panic(expected = "IDs need at least 2 repeats to be invalid, not 1")]
+    fn single_repeat_rejected() {
+        AtLeastRepeats::new(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "IDs need at least 2 repeats to be invalid, not 0")]
+    fn no_repeats_rejected() {
+        ExactRepeats::new(0);
+    }
+
+    #[test]
+    fn palindromes() {
+        assert_eq!(vec![0, 1, 2, 3], ids("0-3", Palindrome));
+        assert_eq!(vec![99, 101, 111], ids("95-115", Palindrome));
+        assert!(Palindrome.matches(12321, DECIMAL));
+        assert!(!Palindrome.matches(12312, DECIMAL));
+        check_against_matches(Palindrome);
+    }
+
+    #[test]
+    fn other_radixes() {
+        // 0b101, 0b111, 0b1001, 0b1111
+        assert_eq!(
+            vec![5, 7, 9, 15],
+            Solver::with_radix("100-1111", 2, Palindrome)
+                .ids()
+                .flat_map(|(_, ids)| ids)
+                .collect::<Vec<_>>(),
+        );
+
+        // The same IDs, written in different radixes
+        let count = |radix| {
+            let s = format!(
+                "{}-{}",
+                crate::format_id(0, radix),
+                crate::format_id(0xffff, radix)
+            );
+            Solver::with_radix(&s, radix, AtLeastRepeats::new(2)).count()
+        };
+        assert_eq!(292, count(2));
+        assert_eq!(270, count(16));
+
+        assert!(!DigitSum(|s| s == 18).matches(0xff, 16));
+        assert!(DigitSum(|s| s == 30).matches(0xff, 16));
+    }
+
+    #[test]
+    fn digit_sums() {
+        let rule = DigitSum(|s| s == 3);
+        assert_eq!(vec![3, 12, 21, 30, 102], ids("0-110", rule));
+        check_against_matches(DigitSum(|s: u32| s.is_multiple_of(7)));
+    }
+}
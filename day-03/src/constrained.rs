@@ -0,0 +1,510 @@
+//! Selecting batteries when the hardware limits which combinations
+//! are allowed. Picking the largest available battery first is no
+//! longer guaranteed to be optimal, so this considers every
+//! possibility, building up the best selections from the end of the
+//! bank.
+
+use crate::{Selection, TooFewBatteries};
+
+#[derive(Debug, Clone, Default)]
+pub struct Constraints {
+    /// No two chosen batteries may be next to each other in the bank.
+    pub no_adjacent: bool,
+    pub block_limit: Option<BlockLimit>,
+    pub budget: Option<Budget>,
+}
+
+impl Constraints {
+    pub const NONE: Self = Self {
+        no_adjacent: false,
+        block_limit: None,
+        budget: None,
+    };
+
+    pub fn is_unconstrained(&self) -> bool {
+        !self.no_adjacent && self.block_limit.is_none() && self.budget.is_none()
+    }
+}
+
+/// The bank is split into consecutive blocks of `size` batteries
+/// (the last may be shorter). At most `max` batteries may be chosen
+/// from each block.
+#[derive(Debug, Copy, Clone)]
+pub struct BlockLimit {
+    pub size: usize,
+    pub max: usize,
+}
+
+/// Each battery has a cost, given in the same order as the bank. The
+/// chosen batteries may cost at most `limit` in total.
+#[derive(Debug, Clone)]
+pub struct Budget {
+    pub costs: Vec<u32>,
+    pub limit: u32,
+}
+
+/// What [`max_joltage`](crate::max_joltage) uses when there are
+/// constraints, only considering selections that satisfy them. If no
+/// such selection of `n_batteries` exists, the error reports the most
+/// batteries that could have been chosen.
+pub fn max_joltage_constrained(
+    batteries: impl IntoIterator<Item = u32>,
+    n_batteries: usize,
+    constraints: &Constraints,
+) -> Result<Selection, TooFewBatteries> {
+    let batteries = batteries.into_iter().collect::<Vec<_>>();
+
+    if let Some(budget) = &constraints.budget {
+        assert_eq!(
+            batteries.len(),
+            budget.costs.len(),
+            "Each battery needs a cost"
+        );
+    }
+    if let Some(block_limit) = constraints.block_limit {
+        assert_ne!(block_limit.size, 0, "Blocks cannot be empty");
+    }
+
+    let table = Table::build(&batteries, n_batteries, constraints);
+    let available = table.most_choosable();
+
+    if available < n_batteries {
+        return Err(TooFewBatteries {
+            needed: n_batteries,
+            available,
+        });
+    }
+
+    let indices = table.selection(n_batteries);
+    let ratings = indices.iter().map(|&i| batteries[i]).collect();
+    Ok(Selection { indices, ratings })
+}
+
+/// Everything that affects which batteries from some index onwards may
+/// still be chosen, apart from how many are left to choose. Parts
+/// whose constraint is off are always zero, so they don't split states
+/// that behave the same.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+struct State {
+    previous_taken: bool,
+    taken_in_block: usize,
+    budget_left: u32,
+}
+
+/// The best choice for every state at every index, for every number
+/// of batteries still to choose. Each layer of `remaining` batteries
+/// only depends on the layer before it, and each index only on the
+/// one after it, so the table is filled from the end of the bank.
+struct Table<'a> {
+    batteries: &'a [u32],
+    constraints: &'a Constraints,
+    /// The most that can be left in the budget. Anything more than the
+    /// whole bank costs is the same as that.
+    budget_limit: u32,
+    /// `take[remaining - 1][slot]`, where `None` means that it's not
+    /// possible to choose enough from the state.
+    take: Vec<Vec<Option<bool>>>,
+}
+
+impl<'a> Table<'a> {
+    fn build(batteries: &'a [u32], n_batteries: usize, constraints: &'a Constraints) -> Self {
+        let budget_limit = constraints.budget.as_ref().map_or(0, |b| {
+            let total = b.costs.iter().map(|&c| u64::from(c)).sum::<u64>();
+            b.limit.min(u32::try_from(total).unwrap_or(u32::MAX))
+        });
+
+        let mut table = Self {
+            batteries,
+            constraints,
+            budget_limit,
+            take: Vec::new(),
+        };
+
+        // Choosing nothing is always possible, and every such
+        // selection is as good as any other.
+        let n_slots = table.slot(batteries.len() + 1, State::default());
+        let mut previous_ranks = vec![Some(0); n_slots];
+
+        for _ in 0..usize::min(n_batteries, batteries.len()) {
+            let (take, ranks) = table.layer(&previous_ranks);
+            table.take.push(take);
+            previous_ranks = ranks;
+        }
+
+        table
+    }
+
+    /// Works out the choices for one more battery than the layer that
+    /// `previous_ranks` describes. The best selection from a state is
+    /// its first rating followed by the best selection from the state
+    /// it moves to, so selections can be compared by that rating and
+    /// the rank of the rest among the previous layer. Returns the
+    /// choices and the ranks of this layer.
+    fn layer(&self, previous_ranks: &[Option<u32>]) -> (Vec<Option<bool>>, Vec<Option<u32>>) {
+        let n_slots = previous_ranks.len();
+        let mut take = vec![None; n_slots];
+        let mut keys = vec![None; n_slots];
+
+        for idx in (0..self.batteries.len()).rev() {
+            for state in self.states() {
+                let slot = self.slot(idx, state);
+                let skip = self
+                    .skip(idx, state)
+                    .and_then(|s| keys[self.slot(idx + 1, s)]);
+                let taken = self.taken(idx, state).and_then(|s| {
+                    let rank = previous_ranks[self.slot(idx + 1, s)]?;
+                    Some((self.batteries[idx], rank))
+                });
+
+                // Equal selections prefer the earlier batteries, which
+                // is always the one that takes this battery.
+                (take[slot], keys[slot]) = match (taken, skip) {
+                    (Some(t), Some(s)) if t < s => (Some(false), Some(s)),
+                    (Some(t), _) => (Some(true), Some(t)),
+                    (None, Some(s)) => (Some(false), Some(s)),
+                    (None, None) => (None, None),
+                };
+            }
+        }
+
+        let mut sorted = keys.iter().flatten().copied().collect::<Vec<_>>();
+        sorted.sort_unstable();
+        sorted.dedup();
+
+        let ranks = keys
+            .iter()
+            .map(|key| {
+                let key = (*key)?;
+                let rank = sorted.binary_search(&key).expect("key was not ranked");
+                Some(u32::try_from(rank).expect("Too many states"))
+            })
+            .collect();
+
+        (take, ranks)
+    }
+
+    /// The most batteries that can be chosen from the start of the
+    /// bank, up to the number the table was built for.
+    fn most_choosable(&self) -> usize {
+        let slot = self.slot(0, self.start());
+        (1..=self.take.len())
+            .rev()
+            .find(|&remaining| self.take[remaining - 1][slot].is_some())
+            .unwrap_or(0)
+    }
+
+    /// Follows the choices from the start of the bank.
+    fn selection(&self, n_batteries: usize) -> Vec<usize> {
+        let mut indices = Vec::with_capacity(n_batteries);
+        let mut state = self.start();
+        let mut remaining = n_batteries;
+        let mut idx = 0;
+
+        while remaining > 0 {
+            let take = self.take[remaining - 1][self.slot(idx, state)];
+            if take.expect("followed an impossible choice") {
+                indices.push(idx);
+                remaining -= 1;
+                state = self.taken(idx, state).expect("took a forbidden battery");
+            } else {
+                state = self.skip(idx, state).expect("skipped past the end");
+            }
+            idx += 1;
+        }
+
+        indices
+    }
+
+    fn start(&self) -> State {
+        State {
+            budget_left: self.budget_limit,
+            ..State::default()
+        }
+    }
+
+    /// Every state, for the constraints that are on.
+    fn states(&self) -> impl Iterator<Item = State> + use<'_> {
+        let Constraints {
+            no_adjacent,
+            block_limit,
+            budget,
+        } = self.constraints;
+
+        let previous = if *no_adjacent { 0..=1 } else { 0..=0 };
+        let block = 0..=block_limit.map_or(0, |b| b.max);
+        let budget = 0..=if budget.is_some() {
+            self.budget_limit
+        } else {
+            0
+        };
+
+        itertools::iproduct!(previous, block, budget).map(|(p, t, b)| State {
+            previous_taken: p == 1,
+            taken_in_block: t,
+            budget_left: b,
+        })
+    }
+
+    /// Where the choice for `state` at `idx` is kept within a layer.
+    fn slot(&self, idx: usize, state: State) -> usize {
+        let n_previous = if self.constraints.no_adjacent { 2 } else { 1 };
+        let n_block = self.constraints.block_limit.map_or(1, |b| b.max + 1);
+        let n_budget = self.budget_limit as usize + 1;
+
+        let mut slot = idx;
+        slot = slot * n_previous + usize::from(state.previous_taken);
+        slot = slot * n_block + state.taken_in_block;
+        slot * n_budget + state.budget_left as usize
+    }
+
+    /// How many have been taken from the block that `idx` is in.
+    fn taken_in_block(&self, idx: usize, state: State) -> usize {
+        match self.constraints.block_limit {
+            Some(b) if idx.is_multiple_of(b.size) => 0,
+            _ => state.taken_in_block,
+        }
+    }
+
+    /// The state after skipping the battery at `idx`, if there is one.
+    fn skip(&self, idx: usize, state: State) -> Option<State> {
+        (idx < self.batteries.len()).then(|| State {
+            previous_taken: false,
+            taken_in_block: self.taken_in_block(idx, state),
+            budget_left: state.budget_left,
+        })
+    }
+
+    /// The state after taking the battery at `idx`, if it may be taken.
+    fn taken(&self, idx: usize, state: State) -> Option<State> {
+        let Constraints {
+            no_adjacent,
+            block_limit,
+            budget,
+        } = self.constraints;
+        let taken_in_block = self.taken_in_block(idx, state);
+
+        if *no_adjacent && state.previous_taken {
+            return None;
+        }
+        if block_limit.is_some_and(|b| taken_in_block >= b.max) {
+            return None;
+        }
+        let budget_left = match budget {
+            Some(b) => state.budget_left.checked_sub(b.costs[idx])?,
+            None => state.budget_left,
+        };
+
+        Some(State {
+            previous_taken: *no_adjacent,
+            taken_in_block: if block_limit.is_some() {
+                taken_in_block + 1
+            } else {
+                0
+            },
+            budget_left,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{max_joltage, max_joltages};
+
+    fn t_max_joltage(batteries: &[u32], n_batteries: usize, constraints: &Constraints) -> Vec<u32> {
+        let selection =
+            max_joltage_constrained(batteries.iter().copied(), n_batteries, constraints)
+                .expect("Too few batteries");
+        selection.ratings
+    }
+
+    /// Every way of choosing `k` indices from `0..n`, in order
+    fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+        if k == 0 {
+            return vec![vec![]];
+        }
+        (0..n)
+            .flat_map(|last| {
+                combinations(last, k - 1).into_iter().map(move |mut c| {
+                    c.push(last);
+                    c
+                })
+            })
+            .collect()
+    }
+
+    fn allowed(indices: &[usize], constraints: &Constraints) -> bool {
+        let adjacent_ok = !constraints.no_adjacent || indices.windows(2).all(|w| w[1] - w[0] > 1);
+        let block_ok = constraints.block_limit.is_none_or(|b| {
+            indices
+                .chunk_by(|a, c| a / b.size == c / b.size)
+                .all(|block| block.len() <= b.max)
+        });
+        let budget_ok = constraints
+            .budget
+            .as_ref()
+            .is_none_or(|b| indices.iter().map(|&i| b.costs[i]).sum::<u32>() <= b.limit);
+
+        adjacent_ok && block_ok && budget_ok
+    }
+
+    #[test]
+    fn unconstrained_matches_greedy() {
+        for l in include_str!("../example.txt").lines() {
+            let batteries = l
+                .chars()
+                .map(|c| c.to_digit(10).unwrap())
+                .collect::<Vec<_>>();
+
+            for n in [2, 12] {
+                let greedy = max_joltage(batteries.iter().copied(), n, &Constraints::NONE).unwrap();
+                let constrained =
+                    max_joltage_constrained(batteries.iter().copied(), n, &Constraints::default());
+                assert_eq!(Ok(greedy), constrained);
+            }
+        }
+    }
+
+    #[test]
+    fn through_max_joltage() {
+        let c = Constraints {
+            no_adjacent: true,
+            ..Default::default()
+        };
+
+        let selection = max_joltage([9, 9, 1, 8], 2, &c).expect("Too few batteries");
+        assert_eq!(vec![0, 3], selection.indices);
+
+        let ratings = max_joltages("9918\n1234", 2, crate::DECIMAL, &c)
+            .map(|s| s.expect("Too few batteries").ratings)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![vec![9, 8], vec![2, 4]], ratings);
+    }
+
+    #[test]
+    fn long_banks() {
+        let c = Constraints {
+            no_adjacent: true,
+            ..Default::default()
+        };
+
+        // Deep enough to overflow the stack when searched recursively
+        let mut batteries = vec![1; 200_000];
+        batteries[7] = 9;
+        batteries[8] = 9;
+        batteries[150_000] = 8;
+        let selection = max_joltage_constrained(batteries, 2, &c).expect("Too few batteries");
+        assert_eq!(vec![7, 150_000], selection.indices);
+
+        // Every other battery, picking the larger of each pair
+        let batteries = (0..2_000).map(|i| [5, 6][i % 2]).collect::<Vec<_>>();
+        let selection = max_joltage_constrained(batteries, 300, &c).expect("Too few batteries");
+        assert_eq!(vec![6; 300], selection.ratings);
+        assert_eq!(
+            (0..300).map(|i| 2 * i + 1).collect::<Vec<_>>(),
+            selection.indices
+        );
+    }
+
+    #[test]
+    fn no_adjacent() {
+        let c = Constraints {
+            no_adjacent: true,
+            ..Default::default()
+        };
+
+        // Greedy would pick 99
+        assert_eq!(vec![9, 8], t_max_joltage(&[9, 9, 1, 8], 2, &c));
+        assert_eq!(
+            Err(TooFewBatteries {
+                needed: 3,
+                available: 2
+            }),
+            max_joltage_constrained([1, 2, 3, 4], 3, &c),
+        );
+    }
+
+    #[test]
+    fn block_limit() {
+        let c = Constraints {
+            block_limit: Some(BlockLimit { size: 3, max: 1 }),
+            ..Default::default()
+        };
+
+        assert_eq!(vec![9, 3], t_max_joltage(&[9, 9, 9, 1, 2, 3], 2, &c));
+        assert_eq!(vec![9, 3, 1], t_max_joltage(&[9, 9, 9, 1, 2, 3, 1], 3, &c));
+    }
+
+    #[test]
+    fn budget() {
+        let c = Constraints {
+            budget: Some(Budget {
+                costs: vec![5, 1, 1],
+                limit: 3,
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(vec![8, 7], t_max_joltage(&[9, 8, 7], 2, &c));
+        assert_eq!(
+            vec![9],
+            t_max_joltage(
+                &[9, 8, 7],
+                1,
+                &Constraints {
+                    budget: Some(Budget {
+                        costs: vec![5, 1, 1],
+                        limit: 5,
+                    }),
+                    ..Default::default()
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn matches_exhaustive_search() {
+        let banks: [&[u32]; 3] = [
+            &[3, 9, 9, 1, 8, 7, 7, 2, 9],
+            &[1, 1, 2, 1, 1, 2, 2, 1, 1, 1],
+            &[5, 4, 3, 2, 1, 9, 8, 7, 6],
+        ];
+
+        for batteries in banks {
+            let costs = batteries.iter().map(|&b| b % 4 + 1).collect::<Vec<_>>();
+
+            for no_adjacent in [false, true] {
+                for block_limit in [None, Some(BlockLimit { size: 4, max: 2 })] {
+                    for budget in [None, Some(8)] {
+                        let c = Constraints {
+                            no_adjacent,
+                            block_limit,
+                            budget: budget.map(|limit| Budget {
+                                costs: costs.clone(),
+                                limit,
+                            }),
+                        };
+
+                        for n in 0..=batteries.len() {
+                            let expected = combinations(batteries.len(), n)
+                                .into_iter()
+                                .filter(|indices| allowed(indices, &c))
+                                .max_by(|a, b| {
+                                    let ratings = |i: &[usize]| {
+                                        i.iter().map(|&i| batteries[i]).collect::<Vec<_>>()
+                                    };
+                                    ratings(a).cmp(&ratings(b)).then_with(|| b.cmp(a))
+                                });
+
+                            let actual = max_joltage_constrained(batteries.iter().copied(), n, &c)
+                                .ok()
+                                .map(|s| s.indices);
+
+                            assert_eq!(expected, actual, "{batteries:?} {n} {c:?}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
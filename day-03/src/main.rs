@@ -1,45 +1,242 @@
+use constrained::Constraints;
+use std::{fmt, ops};
+use subsequence::TooFewItems;
+
+mod constrained;
+mod subsequence;
+
 const INPUT: &str = include_str!("../input.txt");
 
 fn main() {
-    let part1 = sum_of_max_joltage::<2>(INPUT);
+    let part1 = sum_of_max_joltage(INPUT, 2).expect("Could not find joltage");
     // Was preferring the last maximum value instead of first when equal
     assert!(part1 > 16923);
     assert_eq!(17100, part1);
     println!("{part1}");
 
-    let part2 = sum_of_max_joltage::<12>(INPUT);
+    let part2 = sum_of_max_joltage(INPUT, 12).expect("Could not find joltage");
     assert_eq!(170418192256861, part2);
     println!("{part2}");
 }
 
-fn sum_of_max_joltage<const N_BATTERIES: usize>(s: &str) -> u64 {
-    s.lines()
-        .map(|l| l.chars().map(|c| c.to_digit(10).expect("invalid digit")))
-        .map(max_joltage::<N_BATTERIES>)
-        .sum()
+fn sum_of_max_joltage(s: &str, n_batteries: usize) -> Result<u128, JoltageError> {
+    sum_of_max_joltage_in_radix(s, n_batteries, DECIMAL)?
+        .to_u128()
+        .ok_or(JoltageError::Overflow)
+}
+
+/// For when there are too many batteries for the sum to fit in a
+/// `u128`.
+#[cfg_attr(not(test), expect(dead_code))]
+fn sum_of_max_joltage_decimal(s: &str, n_batteries: usize) -> Result<String, JoltageError> {
+    sum_of_max_joltage_in_radix(s, n_batteries, DECIMAL).map(|j| j.to_string())
+}
+
+/// Each battery is rated with a single digit in `radix`, which also
+/// applies to the resulting joltage.
+fn sum_of_max_joltage_in_radix(
+    s: &str,
+    n_batteries: usize,
+    radix: Radix,
+) -> Result<Joltage, JoltageError> {
+    max_joltages(s, n_batteries, radix, &Constraints::NONE)
+        .try_fold(Joltage::zero(radix), |sum, selection| {
+            Ok(sum + selection?.joltage(radix))
+        })
 }
 
-fn max_joltage<const N_BATTERIES: usize>(batteries: impl IntoIterator<Item = u32>) -> u64 {
-    let batteries = batteries.into_iter().collect::<Vec<_>>();
+/// The best selection for each bank, with the same constraints
+/// applying to every bank.
+fn max_joltages(
+    s: &str,
+    n_batteries: usize,
+    radix: Radix,
+    constraints: &Constraints,
+) -> impl Iterator<Item = Result<Selection, JoltageError>> {
+    assert!((2..=36).contains(&radix), "radix {radix} is not supported");
+
+    s.lines().enumerate().map(move |(line_idx, l)| {
+        let line = line_idx + 1;
 
-    let mut start_idx = 0;
-    (0..N_BATTERIES)
-        .map(|n| {
-            let end_idx = batteries.len() - (N_BATTERIES - n - 1);
-            let viable_batteries = &batteries[start_idx..end_idx];
+        let batteries = l
+            .chars()
+            .enumerate()
+            .map(|(column_idx, c)| {
+                c.to_digit(radix).ok_or(JoltageError::InvalidRating {
+                    line,
+                    column: column_idx + 1,
+                    rating: c,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        max_joltage(batteries, n_batteries, constraints).map_err(
+            |TooFewBatteries { needed, available }| JoltageError::TooFewBatteries {
+                line,
+                needed,
+                available,
+            },
+        )
+    })
+}
 
-            let (max_idx, max) = viable_batteries
-                .iter()
-                .copied()
-                .enumerate()
-                .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
-                .expect("Could not find a maximum");
+/// The chosen batteries and their ratings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Selection {
+    /// Positions within the bank, in ascending order
+    indices: Vec<usize>,
+    ratings: Vec<u32>,
+}
+
+impl Selection {
+    fn joltage(&self, radix: Radix) -> Joltage {
+        Joltage {
+            digits: self.ratings.clone(),
+            radix,
+        }
+    }
+}
+
+/// Without constraints, picking the largest battery that leaves
+/// enough for the rest is optimal. Otherwise every possibility is
+/// searched.
+fn max_joltage(
+    batteries: impl IntoIterator<Item = u32>,
+    n_batteries: usize,
+    constraints: &Constraints,
+) -> Result<Selection, TooFewBatteries> {
+    if !constraints.is_unconstrained() {
+        return constrained::max_joltage_constrained(batteries, n_batteries, constraints);
+    }
+
+    let chosen = subsequence::max_subsequence(batteries, n_batteries)
+        .map_err(|TooFewItems { needed, available }| TooFewBatteries { needed, available })?;
+
+    let (indices, ratings) = chosen.into_iter().unzip();
+    Ok(Selection { indices, ratings })
+}
 
-            start_idx += max_idx + 1;
+/// Any base from 2 to 36, using the digits `0-9` and then `a-z`.
+type Radix = u32;
 
-            max
+const DECIMAL: Radix = 10;
+
+/// The ratings of the selected batteries, most significant first.
+/// This can be arbitrarily long.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Joltage {
+    digits: Vec<u32>,
+    radix: Radix,
+}
+
+impl Joltage {
+    fn zero(radix: Radix) -> Self {
+        Self {
+            digits: Vec::new(),
+            radix,
+        }
+    }
+
+    /// `None` if the joltage does not fit.
+    fn to_u128(&self) -> Option<u128> {
+        self.digits.iter().try_fold(0u128, |sum, &n| {
+            sum.checked_mul(self.radix.into())?.checked_add(n.into())
         })
-        .fold(0, |sum, n| sum * 10 + u64::from(n))
+    }
+}
+
+impl ops::Add for Joltage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        let radix = self.radix;
+        assert_eq!(
+            radix, other.radix,
+            "Cannot add joltages in different radixes"
+        );
+
+        // Add from the least significant digit
+        let mut l = self.digits.into_iter().rev();
+        let mut r = other.digits.into_iter().rev();
+        let mut carry = 0;
+        let mut digits = Vec::new();
+
+        loop {
+            let (a, b) = (l.next(), r.next());
+            if a.is_none() && b.is_none() && carry == 0 {
+                break;
+            }
+
+            let d = a.unwrap_or(0) + b.unwrap_or(0) + carry;
+            digits.push(d % radix);
+            carry = d / radix;
+        }
+
+        digits.reverse();
+        Joltage { digits, radix }
+    }
+}
+
+impl fmt::Display for Joltage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut digits = self.digits.iter().skip_while(|&&d| d == 0).peekable();
+
+        if digits.peek().is_none() {
+            return write!(f, "0");
+        }
+
+        for &d in digits {
+            let d = char::from_digit(d, self.radix).expect("invalid digit");
+            write!(f, "{d}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct TooFewBatteries {
+    needed: usize,
+    available: usize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum JoltageError {
+    TooFewBatteries {
+        /// 1-based
+        line: usize,
+        needed: usize,
+        available: usize,
+    },
+    InvalidRating {
+        /// 1-based
+        line: usize,
+        /// 1-based, in characters
+        column: usize,
+        rating: char,
+    },
+    Overflow,
+}
+
+impl fmt::Display for JoltageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JoltageError::TooFewBatteries {
+                line,
+                needed,
+                available,
+            } => write!(
+                f,
+                "Bank on line {line} has {available} batteries but {needed} are needed"
+            ),
+            JoltageError::InvalidRating {
+                line,
+                column,
+                rating,
+            } => write!(f, "{line}:{column}: Invalid battery rating `{rating}`"),
+            JoltageError::Overflow => write!(f, "Total joltage does not fit in a u128"),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -48,20 +245,163 @@ mod test {
 
     const EXAMPLE: &str = include_str!("../example.txt");
 
+    fn t_max_joltage(batteries: impl IntoIterator<Item = u32>, n_batteries: usize) -> u128 {
+        let selection =
+            max_joltage(batteries, n_batteries, &Constraints::NONE).expect("Too few batteries");
+        let joltage = selection.joltage(DECIMAL);
+        joltage.to_u128().expect("Joltage too large")
+    }
+
     #[test]
     fn part1_example() {
-        assert_eq!(357, sum_of_max_joltage::<2>(EXAMPLE));
+        assert_eq!(Ok(357), sum_of_max_joltage(EXAMPLE, 2));
     }
 
     #[test]
     fn max_joltage_prefers_first_of_equal() {
-        assert_eq!(66, max_joltage::<2>([6, 6]));
-        assert_eq!(66, max_joltage::<2>([6, 1, 6]));
-        assert_eq!(66, max_joltage::<2>([6, 1, 6, 1]));
+        assert_eq!(66, t_max_joltage([6, 6], 2));
+        assert_eq!(66, t_max_joltage([6, 1, 6], 2));
+        assert_eq!(66, t_max_joltage([6, 1, 6, 1], 2));
     }
 
     #[test]
     fn part2_example() {
-        assert_eq!(3121910778619, sum_of_max_joltage::<12>(EXAMPLE));
+        assert_eq!(Ok(3121910778619), sum_of_max_joltage(EXAMPLE, 12));
+    }
+
+    #[test]
+    fn max_joltage_reports_indices() {
+        let selections = max_joltages(EXAMPLE, 2, DECIMAL, &Constraints::NONE)
+            .map(|s| s.expect("Too few batteries").indices)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![vec![0, 1], vec![0, 14], vec![13, 14], vec![6, 11]],
+            selections,
+        );
+
+        let selection =
+            max_joltage([1, 9, 1, 9, 1], 3, &Constraints::NONE).expect("Too few batteries");
+        assert_eq!(vec![1, 3, 4], selection.indices);
+        assert_eq!(vec![9, 9, 1], selection.ratings);
+    }
+
+    #[test]
+    fn max_joltage_matches_windowed_search() {
+        // The original approach: repeatedly search the window of
+        // batteries that leaves enough for the remaining picks.
+        fn windowed(batteries: &[u32], n_batteries: usize) -> Vec<usize> {
+            let mut start_idx = 0;
+            (0..n_batteries)
+                .map(|n| {
+                    let end_idx = batteries.len() - (n_batteries - n - 1);
+                    let (max_idx, _) = batteries[start_idx..end_idx]
+                        .iter()
+                        .enumerate()
+                        .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(&a.0)))
+                        .expect("Could not find a maximum");
+                    let idx = start_idx + max_idx;
+                    start_idx = idx + 1;
+                    idx
+                })
+                .collect()
+        }
+
+        for l in EXAMPLE
+            .lines()
+            .chain(["5454545412121", "1111111", "98765123456789"])
+        {
+            let batteries = l
+                .chars()
+                .map(|c| c.to_digit(10).unwrap())
+                .collect::<Vec<_>>();
+
+            for n in 0..=batteries.len() {
+                let selection =
+                    max_joltage(batteries.iter().copied(), n, &Constraints::NONE).unwrap();
+                assert_eq!(windowed(&batteries, n), selection.indices, "{l} {n}");
+            }
+        }
+    }
+
+    #[test]
+    fn too_few_batteries() {
+        assert_eq!(
+            Err(TooFewBatteries {
+                needed: 3,
+                available: 2
+            }),
+            max_joltage([1, 2], 3, &Constraints::NONE),
+        );
+
+        assert_eq!(
+            Err(JoltageError::TooFewBatteries {
+                line: 2,
+                needed: 4,
+                available: 3
+            }),
+            sum_of_max_joltage("1234\n123\n12", 4),
+        );
+
+        assert_eq!(Ok(0), sum_of_max_joltage("123", 0));
+    }
+
+    #[test]
+    fn large_joltages() {
+        let bank = "9".repeat(40);
+        let input = format!("{bank}\n{bank}");
+
+        assert_eq!(
+            Ok(999_999_999_999_999_999_999_999_999_999_999_999 * 2),
+            sum_of_max_joltage(&input, 36),
+        );
+        assert_eq!(Err(JoltageError::Overflow), sum_of_max_joltage(&input, 39));
+        assert_eq!(
+            Ok(format!("1{}8", "9".repeat(39))),
+            sum_of_max_joltage_decimal(&input, 40),
+        );
+        assert_eq!(
+            Ok("3121910778619".into()),
+            sum_of_max_joltage_decimal(EXAMPLE, 12)
+        );
+        assert_eq!(Ok("0".into()), sum_of_max_joltage_decimal("000", 2));
+    }
+
+    #[test]
+    fn other_radixes() {
+        let hex = "1f0a\n9e9e";
+        let sum = sum_of_max_joltage_in_radix(hex, 2, 16).expect("Could not find joltage");
+        assert_eq!(Some(0xfa + 0xee), sum.to_u128());
+        assert_eq!("1e8", sum.to_string());
+
+        let sum = sum_of_max_joltage_in_radix("1011\n0110", 3, 2).expect("Could not find joltage");
+        assert_eq!(Some(0b111 + 0b110), sum.to_u128());
+        assert_eq!("1101", sum.to_string());
+
+        let sum = sum_of_max_joltage_in_radix("azz9", 2, 36).expect("Could not find joltage");
+        assert_eq!("zz", sum.to_string());
+    }
+
+    #[test]
+    fn invalid_ratings() {
+        assert_eq!(
+            Err(JoltageError::InvalidRating {
+                line: 2,
+                column: 3,
+                rating: 'x'
+            }),
+            sum_of_max_joltage("123\n45x6", 2),
+        );
+        assert_eq!(
+            Err(JoltageError::InvalidRating {
+                line: 1,
+                column: 4,
+                rating: 'g'
+            }),
+            sum_of_max_joltage_in_radix("abcg", 2, 16),
+        );
+        assert_eq!(
+            "2:3: Invalid battery rating `x`",
+            sum_of_max_joltage("123\n45x6", 2).unwrap_err().to_string(),
+        );
     }
 }
@@ -0,0 +1,138 @@
+//! Picking `k` items, keeping their original order, so that the
+//! result is as large as possible when compared lexicographically.
+
+use std::cmp::Ordering;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TooFewItems {
+    pub needed: usize,
+    pub available: usize,
+}
+
+/// The lexicographically largest subsequence of `k` items. When
+/// several subsequences are equal, the one using the earliest items
+/// is chosen.
+///
+/// Each chosen item is returned with its position in `items`.
+pub fn max_subsequence<T: Ord>(
+    items: impl IntoIterator<Item = T>,
+    k: usize,
+) -> Result<Vec<(usize, T)>, TooFewItems> {
+    max_subsequence_by(items, k, T::cmp)
+}
+
+/// The lexicographically smallest subsequence of `k` items. When
+/// several subsequences are equal, the one using the earliest items
+/// is chosen.
+#[cfg_attr(not(test), expect(dead_code))]
+pub fn min_subsequence<T: Ord>(
+    items: impl IntoIterator<Item = T>,
+    k: usize,
+) -> Result<Vec<(usize, T)>, TooFewItems> {
+    max_subsequence_by(items, k, |a, b| b.cmp(a))
+}
+
+/// Like [`max_subsequence`], but items are compared with `cmp`.
+///
+/// This runs in linear time: each item is added to and removed from
+/// the selection at most once.
+pub fn max_subsequence_by<T>(
+    items: impl IntoIterator<Item = T>,
+    k: usize,
+    mut cmp: impl FnMut(&T, &T) -> Ordering,
+) -> Result<Vec<(usize, T)>, TooFewItems> {
+    let items = items.into_iter().collect::<Vec<_>>();
+
+    let Some(mut n_to_skip) = items.len().checked_sub(k) else {
+        return Err(TooFewItems {
+            needed: k,
+            available: items.len(),
+        });
+    };
+
+    // Keep the chosen items in non-increasing order. A larger item
+    // replaces any smaller ones before it, as long as there are
+    // enough items left afterwards. Equal items are kept, preferring
+    // the first of them.
+    let mut chosen = Vec::<(usize, T)>::with_capacity(items.len());
+
+    for (idx, item) in items.into_iter().enumerate() {
+        while n_to_skip > 0
+            && chosen
+                .last()
+                .is_some_and(|(_, c)| cmp(c, &item) == Ordering::Less)
+        {
+            chosen.pop();
+            n_to_skip -= 1;
+        }
+        chosen.push((idx, item));
+    }
+
+    // Any skips that weren't needed come off the end
+    chosen.truncate(k);
+
+    Ok(chosen)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn items<T>(chosen: Result<Vec<(usize, T)>, TooFewItems>) -> Vec<T> {
+        chosen
+            .expect("Too few items")
+            .into_iter()
+            .map(|(_, i)| i)
+            .collect()
+    }
+
+    #[test]
+    fn max_subsequence_exercise() {
+        assert_eq!(
+            vec!['z', 'y', 'x'],
+            items(max_subsequence("azbycx".chars(), 3))
+        );
+        assert_eq!(
+            vec!["b", "c"],
+            items(max_subsequence(["a", "b", "a", "c"], 2))
+        );
+        assert_eq!(
+            vec![(1, 9), (3, 9)],
+            max_subsequence([1, 9, 1, 9], 2).expect("Too few items"),
+        );
+        assert_eq!(Vec::<u8>::new(), items(max_subsequence([1, 2], 0)));
+    }
+
+    #[test]
+    fn min_subsequence_exercise() {
+        assert_eq!(
+            vec!['a', 'b', 'c'],
+            items(min_subsequence("azbycx".chars(), 3))
+        );
+        assert_eq!(vec![1, 1, 1], items(min_subsequence([3, 1, 2, 1, 1], 3)));
+        // Ties prefer the earliest items
+        assert_eq!(
+            vec![(0, 1), (2, 1)],
+            min_subsequence([1, 5, 1, 1], 2).expect("Too few items"),
+        );
+    }
+
+    #[test]
+    fn max_subsequence_by_exercise() {
+        // Longest words first, ignoring the words themselves
+        let words = ["a", "abc", "ab", "abcd", "b"];
+        let chosen = max_subsequence_by(words, 2, |a, b| a.len().cmp(&b.len()));
+        assert_eq!(vec!["abcd", "b"], items(chosen));
+    }
+
+    #[test]
+    fn too_few_items() {
+        assert_eq!(
+            Err(TooFewItems {
+                needed: 3,
+                available: 2
+            }),
+            max_subsequence([1, 2], 3),
+        );
+    }
+}
@@ -0,0 +1,397 @@
+//! Day 4 as a cellular automaton: the removal of rolls of paper is
+//! one possible rule, but boards can be stepped under others.
+
+use itertools::Itertools;
+use std::collections::HashMap;
+
+use crate::{BitSet, Board};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Neighborhood {
+    /// Cells within `radius` steps horizontally plus vertically
+    VonNeumann { radius: usize },
+    /// Cells within `radius` steps in both directions, including
+    /// diagonals
+    Moore { radius: usize },
+}
+
+impl Neighborhood {
+    fn radius(self) -> usize {
+        match self {
+            Neighborhood::VonNeumann { radius } | Neighborhood::Moore { radius } => radius,
+        }
+    }
+
+    fn offsets(self) -> Vec<(isize, isize)> {
+        let (radius, within): (_, fn(isize, isize, isize) -> bool) = match self {
+            Neighborhood::VonNeumann { radius } => (radius, |dx, dy, r| dx.abs() + dy.abs() <= r),
+            Neighborhood::Moore { radius } => (radius, |dx, dy, r| dx.abs().max(dy.abs()) <= r),
+        };
+        let r = isize::try_from(radius).expect("radius too large");
+
+        (-r..=r)
+            .flat_map(|dy| (-r..=r).map(move |dx| (dx, dy)))
+            .filter(|&(dx, dy)| (dx, dy) != (0, 0) && within(dx, dy, r))
+            .collect()
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Edges {
+    /// Cells beyond the edge are always empty
+    Bounded,
+    /// The board wraps around: the right edge touches the left and
+    /// the bottom touches the top.
+    Toroidal,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+/// Compares the number of occupied neighbors against a value.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Threshold {
+    pub comparison: Comparison,
+    pub value: usize,
+}
+
+impl Threshold {
+    fn matches(self, n_neighbors: usize) -> bool {
+        let v = self.value;
+        match self.comparison {
+            Comparison::Less => n_neighbors < v,
+            Comparison::LessOrEqual => n_neighbors <= v,
+            Comparison::Equal => n_neighbors == v,
+            Comparison::GreaterOrEqual => n_neighbors >= v,
+            Comparison::Greater => n_neighbors > v,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Rules {
+    pub neighborhood: Neighborhood,
+    pub edges: Edges,
+    /// An occupied cell is emptied when this matches
+    pub removal: Option<Threshold>,
+    /// An empty cell is filled when this matches
+    pub birth: Option<Threshold>,
+}
+
+impl Rules {
+    /// The forklifts can reach a roll with fewer than 4 neighbors.
+    pub fn day4() -> Self {
+        Self {
+            neighborhood: Neighborhood::Moore { radius: 1 },
+            edges: Edges::Bounded,
+            removal: Some(Threshold {
+                comparison: Comparison::Less,
+                value: 4,
+            }),
+            birth: None,
+        }
+    }
+}
+
+/// How [`Automaton::run`] stopped.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// No cell changed after this many steps
+    Stable { steps: usize },
+    /// The board after `start` steps reappears every `period` steps
+    Cycle { start: usize, period: usize },
+    /// Neither of the above happened within the limit
+    Unfinished,
+}
+
+#[derive(Debug, Clone)]
+pub struct Automaton {
+    width: usize,
+    height: usize,
+    cells: BitSet,
+    rules: Rules,
+    offsets: Vec<(isize, isize)>,
+}
+
+impl Automaton {
+    pub fn new(board: &Board, rules: Rules) -> Self {
+        let (width, height) = (board.width, board.height);
+        let mut offsets = rules.neighborhood.offsets();
+
+        // When the neighborhood is wider than the board, offsets can
+        // wrap onto the same cell, or back onto the cell itself. Each
+        // cell should only count once.
+        let span = 2 * rules.neighborhood.radius() + 1;
+        if rules.edges == Edges::Toroidal && width > 0 && height > 0 && span > width.min(height) {
+            let (w, h) = (width as isize, height as isize);
+            offsets = offsets
+                .into_iter()
+                .map(|(dx, dy)| (dx.rem_euclid(w), dy.rem_euclid(h)))
+                .filter(|&d| d != (0, 0))
+                .unique()
+                .collect();
+        }
+
+        Self {
+            width,
+            height,
+            cells: board.rolls.clone(),
+            rules,
+            offsets,
+        }
+    }
+
+    /// Panics if the cell is not on the board, rather than wrapping
+    /// onto the next row.
+    pub fn contains(&self, (x, y): (usize, usize)) -> bool {
+        let (width, height) = (self.width, self.height);
+        assert!(
+            x < width && y < height,
+            "({x}, {y}) is outside the {width}x{height} board"
+        );
+        self.cells.get(y * width + x)
+    }
+
+    pub fn n_occupied(&self) -> usize {
+        (0..self.width * self.height)
+            .filter(|&i| self.cells.get(i))
+            .count()
+    }
+
+    fn n_neighbors(&self, x: usize, y: usize) -> usize {
+        let (w, h) = (self.width, self.height);
+        let offsets = self.offsets.iter();
+
+        match self.rules.edges {
+            Edges::Bounded => offsets
+                .filter_map(|&(dx, dy)| {
+                    let nx = x.checked_add_signed(dx).filter(|&nx| nx < w);
+                    let ny = y.checked_add_signed(dy).filter(|&ny| ny < h);
+                    nx.zip(ny)
+                })
+                .filter(|&c| self.contains(c))
+                .count(),
+            Edges::Toroidal => {
+                let wrap = |v: usize, d: isize, len: usize| {
+                    let len = len as isize;
+                    (v as isize + d).rem_euclid(len) as usize
+                };
+
+                offsets
+                    .map(|&(dx, dy)| (wrap(x, dx, w), wrap(y, dy, h)))
+                    .filter(|&c| self.contains(c))
+                    .count()
+            }
+        }
+    }
+
+    /// Applies the rules to every cell at once, returning how many
+    /// cells changed.
+    pub fn step(&mut self) -> usize {
+        let mut next = self.cells.clone();
+        let mut n_changed = 0;
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let occupied = self.contains((x, y));
+                let rule = if occupied {
+                    self.rules.removal
+                } else {
+                    self.rules.birth
+                };
+
+                if rule.is_some_and(|r| r.matches(self.n_neighbors(x, y))) {
+                    next.set(y * self.width + x, !occupied);
+                    n_changed += 1;
+                }
+            }
+        }
+
+        self.cells = next;
+        n_changed
+    }
+
+    /// Steps until the board stops changing or repeats an earlier
+    /// state, giving up after `max_steps`.
+    pub fn run(&mut self, max_steps: usize) -> Outcome {
+        let mut seen = HashMap::from([(self.cells.clone(), 0)]);
+
+        for steps in 1..=max_steps {
+            if self.step() == 0 {
+                return Outcome::Stable { steps: steps - 1 };
+            }
+
+            if let Some(start) = seen.insert(self.cells.clone(), steps) {
+                return Outcome::Cycle {
+                    start,
+                    period: steps - start,
+                };
+            }
+        }
+
+        Outcome::Unfinished
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{accessible_rolls_of_paper, accessible_rolls_of_paper_iterative, parse_board};
+
+    const EXAMPLE: &str = include_str!("../example.txt");
+
+    fn automaton(s: &str, rules: Rules) -> Automaton {
+        Automaton::new(&parse_board(s), rules)
+    }
+
+    #[test]
+    fn day4_rules_match_puzzle() {
+        let mut a = automaton(EXAMPLE, Rules::day4());
+        let initial = a.n_occupied();
+
+        assert_eq!(accessible_rolls_of_paper(EXAMPLE), a.step());
+
+        let mut a = automaton(EXAMPLE, Rules::day4());
+        assert_eq!(Outcome::Stable { steps: 9 }, a.run(100));
+        assert_eq!(
+            accessible_rolls_of_paper_iterative(EXAMPLE),
+            initial - a.n_occupied()
+        );
+    }
+
+    #[test]
+    fn neighborhoods() {
+        assert_eq!(4, Neighborhood::VonNeumann { radius: 1 }.offsets().len());
+        assert_eq!(12, Neighborhood::VonNeumann { radius: 2 }.offsets().len());
+        assert_eq!(8, Neighborhood::Moore { radius: 1 }.offsets().len());
+        assert_eq!(24, Neighborhood::Moore { radius: 2 }.offsets().len());
+    }
+
+    #[test]
+    fn edges() {
+        let board = "@..@\n....\n....\n@..@";
+        let rules = |edges| Rules {
+            edges,
+            removal: Some(Threshold {
+                comparison: Comparison::GreaterOrEqual,
+                value: 3,
+            }),
+            ..Rules::day4()
+        };
+
+        // Each corner only touches the others by wrapping
+        let mut a = automaton(board, rules(Edges::Bounded));
+        assert_eq!(0, a.step());
+        let mut a = automaton(board, rules(Edges::Toroidal));
+        assert_eq!(4, a.step());
+        assert_eq!(0, a.n_occupied());
+    }
+
+    #[test]
+    fn small_torus() {
+        let rules = |radius| Rules {
+            neighborhood: Neighborhood::Moore { radius },
+            edges: Edges::Toroidal,
+            ..Rules::day4()
+        };
+
+        // Every offset reaches one of the other 3 cells, or the cell
+        // itself.
+        for radius in [1, 2, 3] {
+            let a = automaton("@@\n@@", rules(radius));
+            assert_eq!(3, a.offsets.len(), "{radius}");
+            assert_eq!(3, a.n_neighbors(0, 0), "{radius}");
+            assert_eq!(3, a.n_neighbors(1, 1), "{radius}");
+        }
+
+        let a = automaton("@", rules(2));
+        assert_eq!(0, a.n_neighbors(0, 0));
+
+        let a = automaton("@@@", rules(1));
+        assert_eq!(2, a.n_neighbors(1, 0));
+
+        // With fewer than 4 neighbors, everything is removed
+        let mut a = automaton("@@\n@@", rules(1));
+        assert_eq!(4, a.step());
+    }
+
+    #[test]
+    #[should_panic(expected = "(2, 0) is outside the 2x2 board")]
+    fn contains_out_of_bounds() {
+        automaton("@@\n@@", Rules::day4()).contains((2, 0));
+    }
+
+    #[test]
+    fn thresholds() {
+        let t = |comparison| Threshold {
+            comparison,
+            value: 3,
+        };
+
+        let matching = |comparison| {
+            (0..6)
+                .filter(|&n| t(comparison).matches(n))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![0, 1, 2], matching(Comparison::Less));
+        assert_eq!(vec![0, 1, 2, 3], matching(Comparison::LessOrEqual));
+        assert_eq!(vec![3], matching(Comparison::Equal));
+        assert_eq!(vec![3, 4, 5], matching(Comparison::GreaterOrEqual));
+        assert_eq!(vec![4, 5], matching(Comparison::Greater));
+    }
+
+    #[test]
+    fn birth_and_cycles() {
+        // Conway's Game of Life
+        let life = Rules {
+            neighborhood: Neighborhood::Moore { radius: 1 },
+            edges: Edges::Bounded,
+            removal: Some(Threshold {
+                comparison: Comparison::Less,
+                value: 2,
+            }),
+            birth: Some(Threshold {
+                comparison: Comparison::Equal,
+                value: 3,
+            }),
+        };
+
+        // A blinker flips between horizontal and vertical forever
+        let mut a = automaton(".....\n.....\n.@@@.\n.....\n.....", life);
+        a.step();
+        assert!(a.contains((2, 1)) && a.contains((2, 2)) && a.contains((2, 3)));
+        assert_eq!(3, a.n_occupied());
+
+        let mut a = automaton(".....\n.....\n.@@@.\n.....\n.....", life);
+        assert_eq!(
+            Outcome::Cycle {
+                start: 0,
+                period: 2
+            },
+            a.run(10)
+        );
+    }
+
+    #[test]
+    fn unfinished() {
+        let grow = Rules {
+            birth: Some(Threshold {
+                comparison: Comparison::GreaterOrEqual,
+                value: 1,
+            }),
+            removal: None,
+            ..Rules::day4()
+        };
+        let mut a = automaton("@....\n.....\n.....\n.....\n.....", grow);
+        assert_eq!(Outcome::Unfinished, a.run(2));
+        // Continues from where it left off
+        assert_eq!(Outcome::Stable { steps: 2 }, a.run(10));
+        assert_eq!(25, a.n_occupied());
+    }
+}
@@ -0,0 +1,238 @@
+//! Rolls of paper stacked in several layers. The input is one grid
+//! per layer, separated by blank lines, and each roll has up to 26
+//! neighbors in the layers above, below, and its own.
+
+use itertools::Itertools;
+
+use crate::{
+    BitSet, Board, ParseError, ParseErrorKind, RollBoard, parse_grid, remove_until_stable,
+};
+
+pub fn accessible_rolls_of_paper_3d(s: &str) -> usize {
+    let board = parse_layers(s);
+
+    board.accessible_rolls_of_paper().count()
+}
+
+pub fn accessible_rolls_of_paper_iterative_3d(s: &str) -> usize {
+    let mut board = parse_layers(s);
+    let accessible = board.accessible_rolls_of_paper().collect::<Vec<_>>();
+
+    remove_until_stable(&mut board, accessible).len()
+}
+
+type Coordinate = (usize, usize, usize);
+
+#[derive(Debug, Clone)]
+pub struct Board3d {
+    width: usize,
+    height: usize,
+    depth: usize,
+    rolls: BitSet,
+    /// How many of the 26 surrounding cells have a roll
+    n_neighbors: Vec<u8>,
+}
+
+impl Board3d {
+    fn new(width: usize, height: usize, depth: usize) -> Self {
+        let len = width * height * depth;
+        Self {
+            width,
+            height,
+            depth,
+            rolls: BitSet::new(len),
+            n_neighbors: vec![0; len],
+        }
+    }
+
+    fn idx(&self, (x, y, z): Coordinate) -> usize {
+        (z * self.height + y) * self.width + x
+    }
+
+    fn contains(&self, c: Coordinate) -> bool {
+        self.rolls.get(self.idx(c))
+    }
+
+    fn insert(&mut self, c: Coordinate) {
+        let idx = self.idx(c);
+        if self.rolls.get(idx) {
+            return;
+        }
+        self.rolls.set(idx, true);
+
+        for n in self.neighbors(c) {
+            let idx = self.idx(n);
+            self.n_neighbors[idx] += 1;
+        }
+    }
+
+    fn remove(&mut self, c: Coordinate) {
+        let idx = self.idx(c);
+        if !self.rolls.get(idx) {
+            return;
+        }
+        self.rolls.set(idx, false);
+
+        for n in self.neighbors(c) {
+            let idx = self.idx(n);
+            self.n_neighbors[idx] -= 1;
+        }
+    }
+
+    fn is_accessible(&self, c: Coordinate) -> bool {
+        self.contains(c) && self.n_neighbors[self.idx(c)] <= Board::MAX_NEIGHBORS_FOR_ACCESS
+    }
+
+    /// All the in-bounds cells around a cell
+    fn neighbors(&self, (x, y, z): Coordinate) -> impl Iterator<Item = Coordinate> + use<> {
+        const OFFSETS: [isize; 3] = [-1, 0, 1];
+        let (width, height, depth) = (self.width, self.height, self.depth);
+
+        itertools::iproduct!(OFFSETS, OFFSETS, OFFSETS)
+            .filter(|&d| d != (0, 0, 0))
+            .filter_map(move |(dx, dy, dz)| {
+                let nx = x.checked_add_signed(dx).filter(|&nx| nx < width)?;
+                let ny = y.checked_add_signed(dy).filter(|&ny| ny < height)?;
+                let nz = z.checked_add_signed(dz).filter(|&nz| nz < depth)?;
+                Some((nx, ny, nz))
+            })
+    }
+
+    fn accessible_rolls_of_paper(&self) -> impl Iterator<Item = Coordinate> {
+        itertools::iproduct!(0..self.depth, 0..self.height, 0..self.width)
+            .map(|(z, y, x)| (x, y, z))
+            .filter(|&c| self.is_accessible(c))
+    }
+}
+
+impl RollBoard for Board3d {
+    type Coordinate = Coordinate;
+
+    fn is_accessible(&self, c: Coordinate) -> bool {
+        Board3d::is_accessible(self, c)
+    }
+
+    fn remove(&mut self, c: Coordinate) {
+        Board3d::remove(self, c);
+    }
+
+    fn neighbors(&self, c: Coordinate) -> impl Iterator<Item = Coordinate> {
+        Board3d::neighbors(self, c)
+    }
+}
+
+/// Panics with the location of any malformed cell.
+fn parse_layers(s: &str) -> Board3d {
+    try_parse_layers(s).unwrap_or_else(|e| panic!("{e}"))
+}
+
+fn try_parse_layers(s: &str) -> Result<Board3d, ParseError> {
+    let layers = s
+        .lines()
+        .enumerate()
+        .chunk_by(|(_, l)| l.is_empty())
+        .into_iter()
+        .filter(|(is_separator, _)| !is_separator)
+        .map(|(_, layer)| {
+            let layer = layer.collect::<Vec<_>>();
+            let first_line = layer.first().map_or(0, |&(i, _)| i);
+            parse_grid(layer).map(|grid| (first_line, grid))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (width, height) = layers
+        .first()
+        .map_or((0, 0), |(_, grid)| (grid.width, grid.height));
+    let mut board = Board3d::new(width, height, layers.len());
+
+    for (z, (first_line, grid)) in layers.into_iter().enumerate() {
+        if (grid.width, grid.height) != (width, height) {
+            return Err(ParseError {
+                line: first_line + 1,
+                column: 1,
+                kind: ParseErrorKind::MismatchedLayer {
+                    expected: (width, height),
+                    actual: (grid.width, grid.height),
+                },
+            });
+        }
+
+        for (x, y) in grid.rolls {
+            board.insert((x, y, z));
+        }
+    }
+
+    Ok(board)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{accessible_rolls_of_paper, accessible_rolls_of_paper_iterative};
+
+    const EXAMPLE: &str = include_str!("../example.txt");
+
+    #[test]
+    fn single_layer_matches_2d() {
+        assert_eq!(
+            accessible_rolls_of_paper(EXAMPLE),
+            accessible_rolls_of_paper_3d(EXAMPLE)
+        );
+        assert_eq!(
+            accessible_rolls_of_paper_iterative(EXAMPLE),
+            accessible_rolls_of_paper_iterative_3d(EXAMPLE),
+        );
+    }
+
+    #[test]
+    fn stacked_layers() {
+        // A solid 3x3x3 cube: the corners have 7 neighbors, so nothing
+        // is accessible.
+        let cube = "@@@\n@@@\n@@@\n\n@@@\n@@@\n@@@\n\n@@@\n@@@\n@@@";
+        assert_eq!(0, accessible_rolls_of_paper_3d(cube));
+        assert_eq!(0, accessible_rolls_of_paper_iterative_3d(cube));
+
+        // Two layers of a line of three: the ends have 3 neighbors,
+        // the middles have 5.
+        let lines = "@@@\n\n@@@";
+        assert_eq!(4, accessible_rolls_of_paper_3d(lines));
+        assert_eq!(6, accessible_rolls_of_paper_iterative_3d(lines));
+
+        // Separated by several blank lines
+        assert_eq!(
+            6,
+            accessible_rolls_of_paper_iterative_3d("\n@@@\n\n\n@@@\n")
+        );
+    }
+
+    #[test]
+    fn example_stacked() {
+        let stacked = [EXAMPLE.trim_end(); 2].join("\n\n");
+        let one_layer = accessible_rolls_of_paper_3d(EXAMPLE);
+
+        // Each roll now has a copy of itself and its neighbors beside
+        // it, so fewer are accessible.
+        assert!(accessible_rolls_of_paper_3d(&stacked) < one_layer * 2);
+    }
+
+    #[test]
+    fn mismatched_layers() {
+        let e = try_parse_layers("@@@\n@@@\n\n@@\n@@").expect_err("Should not parse");
+        assert_eq!(
+            (
+                4,
+                ParseErrorKind::MismatchedLayer {
+                    expected: (3, 2),
+                    actual: (2, 2)
+                }
+            ),
+            (e.line, e.kind),
+        );
+
+        let e = try_parse_layers("@@@\n\n@x@").expect_err("Should not parse");
+        assert_eq!(
+            (3, 2, ParseErrorKind::InvalidCell('x')),
+            (e.line, e.column, e.kind)
+        );
+    }
+}
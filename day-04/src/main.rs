@@ -1,9 +1,26 @@
 use itertools::Itertools;
-use std::collections::BTreeSet;
+use std::fmt;
+
+// Not needed for the puzzle answers
+#[cfg_attr(not(test), expect(dead_code))]
+mod automaton;
+#[cfg_attr(not(test), expect(dead_code))]
+mod layers;
+#[cfg_attr(not(test), expect(dead_code))]
+mod what_if;
 
 const INPUT: &str = include_str!("../input.txt");
 
+// Show how the cascade of removals in part 2 spreads
+const SHOW_CASCADE: bool = false;
+
 fn main() {
+    if SHOW_CASCADE {
+        let cascade = cascade(parse_board(INPUT));
+        eprintln!("{}\n", cascade.render_waves());
+        eprintln!("{}", cascade.render_histogram());
+    }
+
     let part1 = accessible_rolls_of_paper(INPUT);
     assert_eq!(1533, part1);
     println!("{part1}");
@@ -16,63 +33,426 @@ fn main() {
 fn accessible_rolls_of_paper(s: &str) -> usize {
     let board = parse_board(s);
 
-    find_accessible_rolls_of_paper(&board).count()
+    board.n_accessible()
 }
 
 fn accessible_rolls_of_paper_iterative(s: &str) -> usize {
-    let mut board = parse_board(s);
-    let mut to_remove = Vec::new();
-    let mut total_removed = 0;
+    cascade(parse_board(s)).total_removed()
+}
 
-    loop {
-        to_remove.clear();
-        to_remove.extend(find_accessible_rolls_of_paper(&board));
+/// The result of repeatedly removing every accessible roll of paper
+/// until none are left.
+#[derive(Debug, Clone)]
+struct Cascade {
+    /// The round (starting at 1) in which the roll in each cell was
+    /// removed, indexed like the board.
+    waves: Vec<Option<usize>>,
+    /// How many rolls were removed in each round, starting at round 1
+    per_round: Vec<usize>,
+    /// The rolls that could never be removed
+    remaining: Board,
+}
 
-        if to_remove.is_empty() {
-            break;
-        }
+impl Cascade {
+    fn total_removed(&self) -> usize {
+        self.per_round.iter().sum()
+    }
+
+    /// The board with each removed roll replaced by the round it was
+    /// removed in.
+    fn render_waves(&self) -> String {
+        let board = &self.remaining;
+        let width = self.per_round.len().to_string().len();
+
+        (0..board.height)
+            .map(|y| {
+                (0..board.width)
+                    .map(|x| {
+                        let c = (x, y);
+                        match self.waves[board.idx(c)] {
+                            Some(round) => format!("{round:>width$}"),
+                            None if board.contains(c) => format!("{:>width$}", "@"),
+                            None => format!("{:>width$}", "."),
+                        }
+                    })
+                    .join(" ")
+            })
+            .join("\n")
+    }
+
+    /// One line per round with the number of rolls removed and a
+    /// proportional bar.
+    fn render_histogram(&self) -> String {
+        const BAR_WIDTH: usize = 50;
+
+        let max = self.per_round.iter().copied().max().unwrap_or(0);
+        let round_width = self.per_round.len().to_string().len();
+        let count_width = max.to_string().len();
+
+        self.per_round
+            .iter()
+            .enumerate()
+            .map(|(i, &n)| {
+                let round = i + 1;
+                let bar = "#".repeat((n * BAR_WIDTH).div_ceil(max));
+                format!("{round:>round_width$} {n:>count_width$} {bar}")
+            })
+            .join("\n")
+    }
+}
+
+fn cascade(mut board: Board) -> Cascade {
+    let mut waves = vec![None; board.width * board.height];
+    let mut per_round = Vec::new();
+    let mut to_remove = board.accessible_rolls_of_paper().collect::<Vec<_>>();
+    let mut queued = BitSet::new(board.width * board.height);
+
+    while !to_remove.is_empty() {
+        let round = per_round.len() + 1;
 
-        for removed in &to_remove {
+        for &removed in &to_remove {
             board.remove(removed);
+            waves[board.idx(removed)] = Some(round);
+        }
+        per_round.push(to_remove.len());
+
+        // Only the neighbors of removed rolls can have become
+        // accessible.
+        let removed = std::mem::take(&mut to_remove);
+        for &(x, y) in &removed {
+            for n in board.neighbors(x, y) {
+                let idx = board.idx(n);
+                if board.is_accessible(n) && !queued.get(idx) {
+                    queued.set(idx, true);
+                    to_remove.push(n);
+                }
+            }
+        }
+    }
+
+    Cascade {
+        waves,
+        per_round,
+        remaining: board,
+    }
+}
+
+/// A board that rolls can be removed from one at a time, so that
+/// the same cascade works in any number of dimensions.
+trait RollBoard {
+    type Coordinate: Copy;
+
+    fn is_accessible(&self, c: Self::Coordinate) -> bool;
+    fn remove(&mut self, c: Self::Coordinate);
+    fn neighbors(&self, c: Self::Coordinate) -> impl Iterator<Item = Self::Coordinate>;
+}
+
+impl RollBoard for Board {
+    type Coordinate = (usize, usize);
+
+    fn is_accessible(&self, c: Self::Coordinate) -> bool {
+        Board::is_accessible(self, c)
+    }
+
+    fn remove(&mut self, c: Self::Coordinate) {
+        Board::remove(self, c);
+    }
+
+    fn neighbors(&self, (x, y): Self::Coordinate) -> impl Iterator<Item = Self::Coordinate> {
+        Board::neighbors(self, x, y)
+    }
+}
+
+/// Removes every accessible roll in `start`, and then any roll that
+/// becomes accessible as a result, until none are left. Returns the
+/// removed rolls.
+///
+/// The final board doesn't depend on the order that rolls are removed
+/// in, so there's no need to track rounds.
+fn remove_until_stable<B: RollBoard>(
+    board: &mut B,
+    start: impl IntoIterator<Item = B::Coordinate>,
+) -> Vec<B::Coordinate> {
+    let mut removed = Vec::new();
+    let mut to_visit = start.into_iter().collect::<Vec<_>>();
+
+    while let Some(c) = to_visit.pop() {
+        // Already removed through another neighbor
+        if !board.is_accessible(c) {
+            continue;
         }
-        total_removed += to_remove.len();
+
+        board.remove(c);
+        removed.push(c);
+        to_visit.extend(board.neighbors(c).filter(|&n| board.is_accessible(n)));
     }
 
-    total_removed
+    removed
 }
 
-type Board = BTreeSet<(usize, usize)>;
+/// A fixed-size set of bits.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct BitSet(Vec<u64>);
 
+impl BitSet {
+    fn new(len: usize) -> Self {
+        Self(vec![0; len.div_ceil(64)])
+    }
+
+    fn get(&self, idx: usize) -> bool {
+        self.0[idx / 64] & (1 << (idx % 64)) != 0
+    }
+
+    fn set(&mut self, idx: usize, value: bool) {
+        let bit = 1 << (idx % 64);
+        if value {
+            self.0[idx / 64] |= bit;
+        } else {
+            self.0[idx / 64] &= !bit;
+        }
+    }
+
+    /// The indices of the set bits, in ascending order
+    fn ones(&self) -> impl Iterator<Item = usize> {
+        self.0.iter().enumerate().flat_map(|(i, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(i * 64 + bit)
+            })
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Board {
+    width: usize,
+    height: usize,
+    rolls: BitSet,
+    /// How many of the 8 surrounding cells have a roll, kept up to
+    /// date as rolls are added and removed.
+    n_neighbors: Vec<u8>,
+    /// The rolls with few enough neighbors to be reached. An edit can
+    /// only change the cell itself and its neighbors, so this is kept
+    /// up to date in constant time.
+    accessible: BitSet,
+    n_accessible: usize,
+}
+
+impl Board {
+    const MAX_NEIGHBORS_FOR_ACCESS: u8 = 3;
+
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            rolls: BitSet::new(width * height),
+            n_neighbors: vec![0; width * height],
+            accessible: BitSet::new(width * height),
+            n_accessible: 0,
+        }
+    }
+
+    /// Panics if the cell is not on the board, rather than wrapping
+    /// onto the next row.
+    fn idx(&self, (x, y): (usize, usize)) -> usize {
+        let (width, height) = (self.width, self.height);
+        assert!(
+            x < width && y < height,
+            "({x}, {y}) is outside the {width}x{height} board"
+        );
+        y * width + x
+    }
+
+    fn contains(&self, c: (usize, usize)) -> bool {
+        self.rolls.get(self.idx(c))
+    }
+
+    fn insert(&mut self, c @ (x, y): (usize, usize)) {
+        let idx = self.idx(c);
+        if self.rolls.get(idx) {
+            return;
+        }
+        self.rolls.set(idx, true);
+        self.update_access(idx);
+
+        for n in self.neighbors(x, y) {
+            let idx = self.idx(n);
+            self.n_neighbors[idx] += 1;
+            self.update_access(idx);
+        }
+    }
+
+    fn remove(&mut self, c @ (x, y): (usize, usize)) {
+        let idx = self.idx(c);
+        if !self.rolls.get(idx) {
+            return;
+        }
+        self.rolls.set(idx, false);
+        self.update_access(idx);
+
+        for n in self.neighbors(x, y) {
+            let idx = self.idx(n);
+            self.n_neighbors[idx] -= 1;
+            self.update_access(idx);
+        }
+    }
+
+    /// Bring the accessible set in line with the cell's current roll
+    /// and neighbor count.
+    fn update_access(&mut self, idx: usize) {
+        let accessible =
+            self.rolls.get(idx) && self.n_neighbors[idx] <= Self::MAX_NEIGHBORS_FOR_ACCESS;
+
+        if accessible != self.accessible.get(idx) {
+            self.accessible.set(idx, accessible);
+            if accessible {
+                self.n_accessible += 1;
+            } else {
+                self.n_accessible -= 1;
+            }
+        }
+    }
+
+    fn is_accessible(&self, c: (usize, usize)) -> bool {
+        self.accessible.get(self.idx(c))
+    }
+
+    fn n_accessible(&self) -> usize {
+        self.n_accessible
+    }
+
+    /// All the in-bounds cells around a cell
+    fn neighbors(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> + use<> {
+        let (width, height) = (self.width, self.height);
+
+        neighbor_offsets().filter_map(move |(dx, dy)| {
+            let nx = x.checked_add_signed(dx).filter(|&nx| nx < width);
+            let ny = y.checked_add_signed(dy).filter(|&ny| ny < height);
+            nx.zip(ny)
+        })
+    }
+
+    fn rolls(&self) -> impl Iterator<Item = (usize, usize)> {
+        (0..self.height)
+            .cartesian_product(0..self.width)
+            .map(|(y, x)| (x, y))
+            .filter(|&c| self.contains(c))
+    }
+
+    fn accessible_rolls_of_paper(&self) -> impl Iterator<Item = (usize, usize)> {
+        self.accessible
+            .ones()
+            .map(|idx| (idx % self.width, idx / self.width))
+    }
+}
+
+/// Panics with the location of any malformed cell.
 fn parse_board(s: &str) -> Board {
-    let mut board = BTreeSet::new();
+    try_parse_board(s).unwrap_or_else(|e| panic!("{e}"))
+}
+
+fn try_parse_board(s: &str) -> Result<Board, ParseError> {
+    let grid = parse_grid(s.lines().enumerate())?;
+    let mut board = Board::new(grid.width, grid.height);
+
+    for roll in grid.rolls {
+        board.insert(roll);
+    }
+
+    Ok(board)
+}
+
+/// The rolls from a rectangular grid of `.` and `@`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Grid {
+    width: usize,
+    height: usize,
+    rolls: Vec<(usize, usize)>,
+}
+
+/// Each line comes with its zero-based line number in the input, for
+/// error reporting.
+fn parse_grid<'a>(lines: impl IntoIterator<Item = (usize, &'a str)>) -> Result<Grid, ParseError> {
+    let mut grid = Grid::default();
+
+    for (y, (line_idx, l)) in lines.into_iter().enumerate() {
+        let error = |column_idx, kind| ParseError {
+            line: line_idx + 1,
+            column: column_idx + 1,
+            kind,
+        };
 
-    for (y, l) in s.lines().enumerate() {
+        let mut width = 0;
         for (x, c) in l.chars().enumerate() {
-            if c == '@' {
-                board.insert((x, y));
+            match c {
+                '@' => grid.rolls.push((x, y)),
+                '.' => {}
+                c => return Err(error(x, ParseErrorKind::InvalidCell(c))),
             }
+            width += 1;
         }
+
+        if y == 0 {
+            grid.width = width;
+        } else if width != grid.width {
+            let kind = ParseErrorKind::RaggedLine {
+                expected: grid.width,
+                actual: width,
+            };
+            return Err(error(usize::min(width, grid.width), kind));
+        }
+
+        grid.height += 1;
     }
 
-    board
+    Ok(grid)
 }
 
-fn find_accessible_rolls_of_paper(board: &Board) -> impl Iterator<Item = (usize, usize)> {
-    board
-        .iter()
-        .filter(|(x, y)| {
-            let occupied_neighbors = neighbor_offsets()
-                .filter(|&(dx, dy)| {
-                    let nx = x.checked_add_signed(dx);
-                    let ny = y.checked_add_signed(dy);
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ParseError {
+    /// 1-based
+    line: usize,
+    /// 1-based, in characters
+    column: usize,
+    kind: ParseErrorKind,
+}
 
-                    nx.zip(ny).is_some_and(|c| board.contains(&c))
-                })
-                .count();
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseErrorKind {
+    InvalidCell(char),
+    RaggedLine {
+        expected: usize,
+        actual: usize,
+    },
+    MismatchedLayer {
+        expected: (usize, usize),
+        actual: (usize, usize),
+    },
+}
 
-            occupied_neighbors < 4
-        })
-        .copied()
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { line, column, kind } = self;
+        write!(f, "{line}:{column}: ")?;
+
+        match kind {
+            ParseErrorKind::InvalidCell(c) => {
+                write!(f, "Invalid cell `{c}`, expected `.` or `@`")
+            }
+            ParseErrorKind::RaggedLine { expected, actual } => {
+                write!(f, "Line is {actual} cells wide but should be {expected}")
+            }
+            ParseErrorKind::MismatchedLayer {
+                expected: (ew, eh),
+                actual: (aw, ah),
+            } => write!(f, "Layer is {aw}x{ah} but should be {ew}x{eh}"),
+        }
+    }
 }
 
 fn neighbor_offsets() -> impl Iterator<Item = (isize, isize)> {
@@ -87,9 +467,36 @@ fn neighbor_offsets() -> impl Iterator<Item = (isize, isize)> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::collections::BTreeSet;
 
     const EXAMPLE: &str = include_str!("../example.txt");
 
+    /// A deterministic stream of pseudo-random numbers
+    pub fn xorshift(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    /// A deterministic jumble of rolls, about 70% full
+    pub fn random_board(width: usize, height: usize, seed: u64) -> String {
+        let mut state = seed;
+        (0..height)
+            .map(|_| {
+                (0..width)
+                    .map(|_| {
+                        if xorshift(&mut state) % 10 < 7 {
+                            '@'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .join("\n")
+    }
+
     #[test]
     fn part1_example() {
         assert_eq!(13, accessible_rolls_of_paper(EXAMPLE));
@@ -104,4 +511,207 @@ mod test {
     fn part2_example() {
         assert_eq!(43, accessible_rolls_of_paper_iterative(EXAMPLE));
     }
+
+    #[test]
+    fn strict_parsing() {
+        fn error(s: &str) -> (usize, usize, ParseErrorKind) {
+            let e = try_parse_board(s).expect_err("Should not parse");
+            (e.line, e.column, e.kind)
+        }
+
+        use ParseErrorKind::*;
+        assert_eq!((2, 3, InvalidCell('x')), error("@.@\n.@x\n@@@"));
+        assert_eq!((1, 1, InvalidCell(' ')), error(" @"));
+        assert_eq!(
+            (
+                3,
+                3,
+                RaggedLine {
+                    expected: 3,
+                    actual: 2
+                }
+            ),
+            error("@.@\n.@.\n@@"),
+        );
+        assert_eq!(
+            (
+                2,
+                3,
+                RaggedLine {
+                    expected: 2,
+                    actual: 3
+                }
+            ),
+            error("@.\n.@."),
+        );
+
+        assert_eq!(
+            "2:3: Invalid cell `x`, expected `.` or `@`",
+            try_parse_board("@.@\n.@x").unwrap_err().to_string(),
+        );
+
+        let board = try_parse_board("@.\n.@\n").expect("Should parse");
+        assert_eq!((2, 2), (board.width, board.height));
+    }
+
+    #[test]
+    fn incremental_edits() {
+        fn rescanned(board: &Board) -> Vec<(usize, usize)> {
+            board
+                .rolls()
+                .filter(|&(x, y)| {
+                    let n = board.neighbors(x, y).filter(|&n| board.contains(n)).count();
+                    n < 4
+                })
+                .collect()
+        }
+
+        let mut board = parse_board(EXAMPLE);
+        assert_eq!(13, board.n_accessible());
+
+        // A deterministic sequence of edits, checking the accessible
+        // set against a full rescan after each one.
+        let mut seed = 0x9e37_79b9_7f4a_7c15_u64;
+        for _ in 0..500 {
+            let seed = xorshift(&mut seed);
+
+            let c = ((seed % 10) as usize, ((seed >> 8) % 10) as usize);
+            if seed >> 32 & 1 == 0 {
+                board.insert(c);
+            } else {
+                board.remove(c);
+            }
+
+            let expected = rescanned(&board);
+            assert_eq!(expected.len(), board.n_accessible());
+            assert_eq!(
+                expected,
+                board.accessible_rolls_of_paper().collect::<Vec<_>>()
+            );
+        }
+
+        let mut board = Board::new(3, 3);
+        assert_eq!(0, board.n_accessible());
+        board.insert((1, 1));
+        board.insert((1, 1));
+        assert_eq!(1, board.n_accessible());
+        for c in [(0, 0), (2, 0), (0, 2)] {
+            board.insert(c);
+        }
+        assert_eq!(4, board.n_accessible());
+        board.insert((2, 2));
+        assert!(!board.is_accessible((1, 1)));
+        assert_eq!(4, board.n_accessible());
+        board.remove((0, 0));
+        board.remove((0, 0));
+        assert!(board.is_accessible((1, 1)));
+        assert_eq!(4, board.n_accessible());
+    }
+
+    #[test]
+    #[should_panic(expected = "(10, 3) is outside the 10x10 board")]
+    fn insert_out_of_bounds() {
+        parse_board(EXAMPLE).insert((10, 3));
+    }
+
+    #[test]
+    #[should_panic(expected = "(3, 10) is outside the 10x10 board")]
+    fn remove_out_of_bounds() {
+        parse_board(EXAMPLE).remove((3, 10));
+    }
+
+    #[test]
+    fn cascade_example() {
+        let cascade = cascade(parse_board(EXAMPLE));
+
+        assert_eq!(vec![13, 12, 7, 5, 2, 1, 1, 1, 1], cascade.per_round);
+        assert_eq!(43, cascade.total_removed());
+        assert_eq!(71 - 43, cascade.remaining.rolls().count());
+
+        assert_eq!(
+            [
+                ". . 1 1 . 1 1 2 1 .",
+                "1 3 4 . 2 . 2 . 3 2",
+                "2 4 5 7 8 . 1 . 3 3",
+                "2 . 6 9 @ @ . . 2 .",
+                "1 3 . @ @ @ @ . 2 1",
+                ". 2 4 @ @ @ @ @ . 2",
+                ". 2 . @ . @ . @ @ 3",
+                "1 . 4 @ @ . @ @ @ 4",
+                ". 2 3 @ @ @ @ @ 5 .",
+                "1 . 1 . @ @ @ . 1 .",
+            ]
+            .join("\n"),
+            cascade.render_waves(),
+        );
+
+        assert_eq!(
+            [
+                "1 13 ##################################################",
+                "2 12 ###############################################",
+                "3  7 ###########################",
+                "4  5 ####################",
+                "5  2 ########",
+                "6  1 ####",
+                "7  1 ####",
+                "8  1 ####",
+                "9  1 ####",
+            ]
+            .join("\n"),
+            cascade.render_histogram(),
+        );
+    }
+
+    #[test]
+    fn part2_matches_rescanning() {
+        // The original implementation, rescanning the whole board
+        // each round.
+        fn rescanning(s: &str) -> usize {
+            let mut board = BTreeSet::new();
+            for (y, l) in s.lines().enumerate() {
+                for (x, c) in l.chars().enumerate() {
+                    if c == '@' {
+                        board.insert((x, y));
+                    }
+                }
+            }
+
+            let mut total_removed = 0;
+            loop {
+                let to_remove = board
+                    .iter()
+                    .copied()
+                    .filter(|&(x, y): &(usize, usize)| {
+                        let occupied_neighbors = neighbor_offsets()
+                            .filter(|&(dx, dy)| {
+                                let nx = x.checked_add_signed(dx);
+                                let ny = y.checked_add_signed(dy);
+                                nx.zip(ny).is_some_and(|c| board.contains(&c))
+                            })
+                            .count();
+                        occupied_neighbors < 4
+                    })
+                    .collect::<Vec<_>>();
+
+                if to_remove.is_empty() {
+                    break total_removed;
+                }
+                for r in &to_remove {
+                    board.remove(r);
+                }
+                total_removed += to_remove.len();
+            }
+        }
+
+        let board = random_board(70, 40, 0x2545_f491_4f6c_dd1d);
+
+        assert_eq!(
+            rescanning(EXAMPLE),
+            accessible_rolls_of_paper_iterative(EXAMPLE)
+        );
+        assert_eq!(
+            rescanning(&board),
+            accessible_rolls_of_paper_iterative(&board)
+        );
+    }
 }
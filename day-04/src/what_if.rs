@@ -0,0 +1,112 @@
+//! How much further would the cascade of removals go if one extra
+//! roll of paper was removed by hand first?
+
+use crate::{Board, cascade, remove_until_stable};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WhatIf {
+    pub roll: (usize, usize),
+    /// Rolls removed by the cascade that otherwise would have
+    /// stayed, not counting `roll` itself.
+    pub extra_removed: usize,
+}
+
+/// Every roll on the board, ranked by how many extra rolls removing
+/// it would free, most first. Ties are ordered by position.
+///
+/// Removing rolls never makes another roll inaccessible, so the
+/// cascade without any help is run once and shared by every query.
+/// Rolls that it removes anyway free nothing extra, and the rest only
+/// need the (usually small) cascade starting from the stable board.
+pub fn what_if_analysis(board: Board) -> Vec<WhatIf> {
+    let all_rolls = board.rolls().collect::<Vec<_>>();
+    let mut remaining = cascade(board).remaining;
+
+    let mut analysis = all_rolls
+        .into_iter()
+        .map(|roll| {
+            let extra_removed = if remaining.contains(roll) {
+                extra_removed(&mut remaining, roll)
+            } else {
+                0
+            };
+
+            WhatIf {
+                roll,
+                extra_removed,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    analysis.sort_by_key(|w| (std::cmp::Reverse(w.extra_removed), w.roll.1, w.roll.0));
+    analysis
+}
+
+/// Removes `roll` from a stable board and counts how many others
+/// follow it. The board is restored afterwards.
+fn extra_removed(board: &mut Board, roll: (usize, usize)) -> usize {
+    board.remove(roll);
+    let removed = remove_until_stable(board, board.neighbors(roll.0, roll.1));
+
+    for &r in removed.iter().chain([&roll]) {
+        board.insert(r);
+    }
+
+    removed.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_board;
+
+    const EXAMPLE: &str = include_str!("../example.txt");
+
+    /// Re-runs the whole cascade for every roll
+    fn brute_force(s: &str) -> Vec<WhatIf> {
+        let board = parse_board(s);
+        let baseline = cascade(board.clone());
+
+        let mut analysis = board
+            .rolls()
+            .map(|roll| {
+                let mut b = board.clone();
+                b.remove(roll);
+                let total = cascade(b).total_removed() + 1;
+                let would_stay = usize::from(baseline.remaining.contains(roll));
+
+                WhatIf {
+                    roll,
+                    extra_removed: total - baseline.total_removed() - would_stay,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        analysis.sort_by_key(|w| (std::cmp::Reverse(w.extra_removed), w.roll.1, w.roll.0));
+        analysis
+    }
+
+    #[test]
+    fn example() {
+        let analysis = what_if_analysis(parse_board(EXAMPLE));
+
+        assert_eq!(71, analysis.len());
+        assert_eq!(brute_force(EXAMPLE), analysis);
+
+        // Removing most of the 28 rolls that normally stay frees all
+        // of the others.
+        let (freeing, rest) = analysis.split_at(24);
+        assert!(freeing.iter().all(|w| w.extra_removed == 27));
+        assert!(rest.iter().all(|w| w.extra_removed == 0));
+        assert_eq!((4, 3), freeing[0].roll);
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let board = crate::test::random_board(30, 20, 0x9e37_79b9_7f4a_7c15);
+
+        let analysis = what_if_analysis(parse_board(&board));
+        assert!(analysis[0].extra_removed > analysis.last().unwrap().extra_removed);
+        assert_eq!(brute_force(&board), analysis);
+    }
+}
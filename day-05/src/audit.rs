@@ -0,0 +1,206 @@
+//! Which fresh ranges are responsible for what.
+
+use itertools::Itertools;
+
+use crate::{Id, IdRange, parse_input};
+
+/// The ranges that make an ingredient fresh
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    pub id: Id,
+    /// The 1-based line numbers of the ranges containing the ID, in
+    /// order. Empty if the ingredient is spoiled.
+    pub lines: Vec<usize>,
+}
+
+impl Explanation {
+    pub fn is_fresh(&self) -> bool {
+        !self.lines.is_empty()
+    }
+}
+
+/// One explanation for each ingredient ID, in input order.
+pub fn explain_ingredients(s: &str) -> Vec<Explanation> {
+    let input = parse_input(s);
+
+    // Sorted by start
+    let mut ranges = input.ranges;
+    ranges.sort_by_key(|(_, r)| *r.start());
+
+    input
+        .ids
+        .into_iter()
+        .map(|id| {
+            // Only ranges starting at or before the ID can contain it
+            let n_candidates = ranges.partition_point(|(_, r)| *r.start() <= id);
+            let lines = ranges[..n_candidates]
+                .iter()
+                .filter(|(_, r)| *r.end() >= id)
+                .map(|&(line, _)| line)
+                .sorted()
+                .collect();
+
+            Explanation { id, lines }
+        })
+        .collect()
+}
+
+/// The ingredient IDs in no fresh range, in input order.
+pub fn spoiled_ingredients(s: &str) -> Vec<Id> {
+    explain_ingredients(s)
+        .into_iter()
+        .filter(|e| !e.is_fresh())
+        .map(|e| e.id)
+        .collect()
+}
+
+/// A maximal run of IDs that are all in the same number of ranges.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub range: IdRange,
+    pub depth: usize,
+}
+
+/// The segments covered by at least one range, in order.
+pub fn coverage_depth(ranges: &[IdRange]) -> Vec<Segment> {
+    // Positions are one past the end of a range, which could be one
+    // past the largest ID.
+    let events = ranges
+        .iter()
+        .filter(|r| !r.is_empty())
+        .flat_map(|r| [(u128::from(*r.start()), 1), (u128::from(*r.end()) + 1, -1)])
+        .sorted()
+        .chunk_by(|&(position, _)| position)
+        .into_iter()
+        .map(|(position, changes)| (position, changes.map(|(_, c)| c).sum::<isize>()))
+        .collect::<Vec<_>>();
+
+    let mut segments = Vec::<Segment>::new();
+    let mut depth = 0;
+
+    for (&(start, change), &(next, _)) in events.iter().tuple_windows() {
+        depth += change;
+        if depth == 0 {
+            continue;
+        }
+
+        let depth = depth.unsigned_abs();
+        let start = Id::try_from(start).expect("segment starts past the last ID");
+        let end = Id::try_from(next - 1).expect("segment ends past the last ID");
+
+        // Changes can cancel out at a position, leaving the same depth
+        match segments.last_mut() {
+            Some(last) if last.depth == depth && last.range.end() + 1 == start => {
+                last.range = *last.range.start()..=end;
+            }
+            _ => segments.push(Segment {
+                range: start..=end,
+                depth,
+            }),
+        }
+    }
+
+    segments
+}
+
+/// The coverage depth of the fresh ranges in the input, one `a-b: n`
+/// line per segment.
+pub fn coverage_depth_report(s: &str) -> String {
+    let ranges = parse_input(s).fresh_ranges().collect::<Vec<_>>();
+
+    coverage_depth(&ranges)
+        .into_iter()
+        .map(|Segment { range, depth }| format!("{}-{}: {depth}", range.start(), range.end()))
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("../example.txt");
+
+    #[test]
+    fn explain_example() {
+        let explained = explain_ingredients(EXAMPLE)
+            .into_iter()
+            .map(|e| (e.id, e.lines))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                (1, vec![]),
+                (5, vec![1]),
+                (8, vec![]),
+                (11, vec![2]),
+                (17, vec![3, 4]),
+                (32, vec![]),
+            ],
+            explained,
+        );
+
+        assert_eq!(vec![1, 8, 32], spoiled_ingredients(EXAMPLE));
+
+        // Line numbers count comments
+        let commented = format!("# Fresh ranges\n{EXAMPLE}");
+        assert_eq!(vec![4, 5], explain_ingredients(&commented)[4].lines);
+    }
+
+    #[test]
+    fn coverage_depth_example() {
+        assert_eq!(
+            [
+                "3-5: 1", "10-11: 1", "12-14: 2", "15-15: 1", "16-18: 2", "19-20: 1",
+            ]
+            .join("\n"),
+            coverage_depth_report(EXAMPLE),
+        );
+    }
+
+    #[test]
+    fn coverage_depth_edges() {
+        fn t_coverage_depth(ranges: &[IdRange]) -> Vec<(IdRange, usize)> {
+            coverage_depth(ranges)
+                .into_iter()
+                .map(|s| (s.range, s.depth))
+                .collect()
+        }
+
+        assert_eq!(Vec::<(IdRange, usize)>::new(), t_coverage_depth(&[]));
+
+        // One range ends where the next begins
+        assert_eq!(vec![(0..=20, 1)], t_coverage_depth(&[0..=10, 11..=20]));
+        assert_eq!(
+            vec![(0..=10, 3)],
+            t_coverage_depth(&[0..=10, 0..=10, 0..=10])
+        );
+        assert_eq!(
+            vec![(0..=4, 1), (5..=Id::MAX, 2)],
+            t_coverage_depth(&[0..=Id::MAX, 5..=Id::MAX]),
+        );
+        assert_eq!(
+            vec![(1..=1, 1), (3..=3, 1)],
+            t_coverage_depth(&[1..=1, 3..=3]),
+        );
+    }
+
+    #[test]
+    fn coverage_depth_matches_brute_force() {
+        let ranges = [0..=9, 5..=14, 5..=5, 20..=30, 25..=25, 31..=40, 12..=21];
+
+        let segments = coverage_depth(&ranges);
+        for id in 0..50 {
+            let expected = ranges.iter().filter(|r| r.contains(&id)).count();
+            let depth = segments
+                .iter()
+                .find(|s| s.range.contains(&id))
+                .map_or(0, |s| s.depth);
+            assert_eq!(expected, depth, "{id}");
+        }
+
+        // Adjacent segments always differ in depth
+        for (a, b) in segments.iter().tuple_windows() {
+            assert!(a.range.end() + 1 < *b.range.start() || a.depth != b.depth);
+        }
+    }
+}
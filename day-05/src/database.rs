@@ -0,0 +1,269 @@
+//! A fresh-range database that can be edited after it's read.
+
+use std::collections::BTreeMap;
+
+use crate::{Id, IdRange, interval_set::IntervalSet};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    Add(IdRange),
+    /// Can split a range in two
+    Remove(IdRange),
+    Query(Id),
+}
+
+/// The state of the database after an operation.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// Whether the ID was fresh, for a query
+    pub fresh: Option<bool>,
+    pub n_fresh: u128,
+}
+
+/// Like an `IntervalSet` but with logarithmic edits, and the number of
+/// fresh IDs kept up to date as they happen.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FreshDatabase {
+    /// Each range's end, by its start. The ranges neither overlap nor
+    /// touch.
+    ranges: BTreeMap<Id, Id>,
+    n_fresh: u128,
+}
+
+impl FreshDatabase {
+    pub fn n_fresh(&self) -> u128 {
+        self.n_fresh
+    }
+
+    pub fn contains(&self, id: Id) -> bool {
+        self.ranges
+            .range(..=id)
+            .next_back()
+            .is_some_and(|(_, &end)| end >= id)
+    }
+
+    pub fn add(&mut self, range: IdRange) {
+        let (mut start, mut end) = range.into_inner();
+        if start > end {
+            return;
+        }
+
+        // A range starting before this one that reaches it
+        if let Some((&s, &e)) = self.ranges.range(..start).next_back()
+            && e.checked_add(1).is_none_or(|e| e >= start)
+        {
+            self.take(s);
+            start = s;
+            end = Id::max(end, e);
+        }
+
+        // Ranges starting within this one, or just after it
+        while let Some((&s, &e)) = self.ranges.range(start..).next()
+            && s <= end.saturating_add(1)
+        {
+            self.take(s);
+            end = Id::max(end, e);
+        }
+
+        self.put(start, end);
+    }
+
+    pub fn remove(&mut self, range: IdRange) {
+        let (start, end) = range.into_inner();
+        if start > end {
+            return;
+        }
+
+        // A range starting before this one that overlaps it keeps the
+        // part before.
+        if let Some((&s, &e)) = self.ranges.range(..start).next_back()
+            && e >= start
+        {
+            self.take(s);
+            self.put(s, start - 1);
+            if e > end {
+                self.put(end + 1, e);
+            }
+        }
+
+        // Ranges starting within this one keep any part after it
+        while let Some((&s, &e)) = self.ranges.range(start..=end).next() {
+            self.take(s);
+            if e > end {
+                self.put(end + 1, e);
+            }
+        }
+    }
+
+    pub fn apply(&mut self, operation: Operation) -> Step {
+        let fresh = match operation {
+            Operation::Add(range) => {
+                self.add(range);
+                None
+            }
+            Operation::Remove(range) => {
+                self.remove(range);
+                None
+            }
+            Operation::Query(id) => Some(self.contains(id)),
+        };
+
+        Step {
+            fresh,
+            n_fresh: self.n_fresh,
+        }
+    }
+
+    /// Applies each operation in turn, giving the state after each one.
+    pub fn run(
+        &mut self,
+        operations: impl IntoIterator<Item = Operation>,
+    ) -> impl Iterator<Item = Step> {
+        operations.into_iter().map(|op| self.apply(op))
+    }
+
+    fn take(&mut self, start: Id) {
+        let end = self.ranges.remove(&start).expect("no range at start");
+        self.n_fresh -= u128::from(end - start) + 1;
+    }
+
+    fn put(&mut self, start: Id, end: Id) {
+        self.ranges.insert(start, end);
+        self.n_fresh += u128::from(end - start) + 1;
+    }
+}
+
+impl From<&IntervalSet> for FreshDatabase {
+    fn from(set: &IntervalSet) -> Self {
+        Self {
+            ranges: set
+                .ranges()
+                .iter()
+                .map(|r| (*r.start(), *r.end()))
+                .collect(),
+            n_fresh: set.cardinality(),
+        }
+    }
+}
+
+/// One operation per line: `add a-b`, `remove a-b` or `query id`.
+pub fn parse_operations(s: &str) -> impl Iterator<Item = Operation> {
+    s.lines().map(|l| {
+        let (op, arg) = l.split_once(' ').expect("malformed operation");
+        let range = || {
+            let (l, u) = arg.split_once("-").expect("malformed fresh range");
+            let [l, u] = [l, u].map(|i| i.parse::<Id>().expect("invalid fresh id"));
+            l..=u
+        };
+
+        match op {
+            "add" => Operation::Add(range()),
+            "remove" => Operation::Remove(range()),
+            "query" => Operation::Query(arg.parse().expect("invalid id")),
+            _ => panic!("unknown operation {op}"),
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{fresh_database, test::xorshift};
+    use std::collections::BTreeSet;
+
+    const EXAMPLE: &str = include_str!("../example.txt");
+
+    #[test]
+    fn example_operations() {
+        let mut db = FreshDatabase::from(&fresh_database(EXAMPLE));
+        assert_eq!(14, db.n_fresh());
+
+        let operations = parse_operations(
+            "query 5\nremove 12-18\nquery 15\nquery 11\nadd 4-12\nremove 0-3\nadd 0-100\nremove 50-50",
+        );
+        let steps = db
+            .run(operations)
+            .map(|s| (s.fresh, s.n_fresh))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                (Some(true), 14),
+                (None, 7),
+                (Some(false), 7),
+                (Some(true), 7),
+                (None, 12),
+                (None, 11),
+                (None, 101),
+                (None, 100),
+            ],
+            steps,
+        );
+        assert!(!db.contains(50));
+        assert!(db.contains(51));
+    }
+
+    #[test]
+    fn extremes() {
+        let mut db = FreshDatabase::default();
+        db.add(0..=Id::MAX);
+        assert_eq!(1 << 64, db.n_fresh());
+
+        db.remove(0..=0);
+        db.remove(Id::MAX..=Id::MAX);
+        assert_eq!((1 << 64) - 2, db.n_fresh());
+
+        db.add(Id::MAX..=Id::MAX);
+        db.add(0..=0);
+        assert_eq!(1 << 64, db.n_fresh());
+        assert_eq!(1, db.ranges.len());
+
+        #[expect(clippy::reversed_empty_ranges)]
+        let empty = 10..=5;
+        db.remove(empty);
+        assert_eq!(1 << 64, db.n_fresh());
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        const UNIVERSE: Id = 64;
+
+        let mut db = FreshDatabase::default();
+        let mut ids = BTreeSet::new();
+
+        // A deterministic jumble of operations within the universe
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        for _ in 0..2000 {
+            let seed = xorshift(&mut seed);
+
+            let start = seed % UNIVERSE;
+            let end = Id::min(start + (seed >> 16) % 10, UNIVERSE - 1);
+            let operation = match (seed >> 32) % 3 {
+                0 => Operation::Add(start..=end),
+                1 => Operation::Remove(start..=end),
+                _ => Operation::Query(start),
+            };
+
+            let expected_fresh = match &operation {
+                Operation::Add(r) => {
+                    ids.extend(r.clone());
+                    None
+                }
+                Operation::Remove(r) => {
+                    ids.retain(|id| !r.contains(id));
+                    None
+                }
+                Operation::Query(id) => Some(ids.contains(id)),
+            };
+
+            let step = db.apply(operation);
+            assert_eq!(expected_fresh, step.fresh);
+            assert_eq!(ids.len() as u128, step.n_fresh);
+
+            // Still merged
+            for ((_, &e), (&s, _)) in db.ranges.iter().zip(db.ranges.iter().skip(1)) {
+                assert!(e + 1 < s);
+            }
+        }
+    }
+}
@@ -0,0 +1,460 @@
+use std::fmt;
+
+use crate::{Id, IdRange};
+
+/// A set of IDs, stored as sorted ranges that neither overlap nor
+/// touch.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntervalSet {
+    ranges: Vec<IdRange>,
+}
+
+impl IntervalSet {
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ranges(&self) -> &[IdRange] {
+        &self.ranges
+    }
+
+    /// Adds the IDs in `range`, merging it with any ranges it overlaps
+    /// or touches. Empty ranges are ignored.
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn insert(&mut self, range: IdRange) {
+        let (mut start, mut end) = range.into_inner();
+        if start > end {
+            return;
+        }
+
+        // The first range that isn't entirely before, with a gap
+        let first = self
+            .ranges
+            .partition_point(|r| r.end().checked_add(1).is_some_and(|e| e < start));
+        // The first range that is entirely after, with a gap
+        let last =
+            first + self.ranges[first..].partition_point(|r| *r.start() <= end.saturating_add(1));
+
+        if let Some(r) = self.ranges[first..last].first() {
+            start = Id::min(start, *r.start());
+        }
+        if let Some(r) = self.ranges[first..last].last() {
+            end = Id::max(end, *r.end());
+        }
+
+        self.ranges.splice(first..last, [start..=end]);
+    }
+
+    pub fn contains(&self, id: Id) -> bool {
+        let idx = self.ranges.partition_point(|r| *r.end() < id);
+        self.ranges.get(idx).is_some_and(|r| *r.start() <= id)
+    }
+
+    /// How many of `ids` are in the set. Already sorted IDs are
+    /// checked in a single pass alongside the ranges, otherwise each
+    /// one is a binary search.
+    pub fn n_contained(&self, ids: &[Id]) -> usize {
+        if ids.is_sorted() {
+            self.contains_sorted(ids.iter().copied())
+                .filter(|&c| c)
+                .count()
+        } else {
+            ids.iter().filter(|&&id| self.contains(id)).count()
+        }
+    }
+
+    /// Whether each of `ids` is in the set. Panics if the IDs are not in
+    /// ascending order.
+    pub fn contains_sorted(&self, ids: impl IntoIterator<Item = Id>) -> impl Iterator<Item = bool> {
+        let mut ranges = self.ranges.iter().peekable();
+        let mut previous = Id::MIN;
+
+        ids.into_iter().map(move |id| {
+            assert!(
+                previous <= id,
+                "IDs are not sorted: {id} follows {previous}"
+            );
+            previous = id;
+
+            while ranges.next_if(|r| *r.end() < id).is_some() {}
+            ranges.peek().is_some_and(|r| *r.start() <= id)
+        })
+    }
+
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn union(&self, other: &Self) -> Self {
+        self.ranges.iter().chain(&other.ranges).cloned().collect()
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut ranges = Vec::new();
+        let (mut a, mut b) = (
+            self.ranges.iter().peekable(),
+            other.ranges.iter().peekable(),
+        );
+
+        while let (Some(l), Some(r)) = (a.peek(), b.peek()) {
+            let start = Id::max(*l.start(), *r.start());
+            let end = Id::min(*l.end(), *r.end());
+            if start <= end {
+                ranges.push(start..=end);
+            }
+
+            // Whichever ends first can't overlap anything else
+            if l.end() < r.end() {
+                a.next();
+            } else {
+                b.next();
+            }
+        }
+
+        // Intersections of disjoint, non-touching ranges can't touch
+        // each other either.
+        Self { ranges }
+    }
+
+    #[cfg_attr(not(test), expect(dead_code))]
+    pub fn difference(&self, other: &Self) -> Self {
+        self.intersection(&other.complement(Id::MIN..=Id::MAX))
+    }
+
+    /// The smallest range containing the whole set.
+    pub fn hull(&self) -> Option<IdRange> {
+        let first = self.ranges.first()?;
+        let last = self.ranges.last()?;
+        Some(*first.start()..=*last.end())
+    }
+
+    /// The IDs between the first and last ranges that are not in the
+    /// set.
+    pub fn gaps(&self) -> Self {
+        match self.hull() {
+            Some(hull) => self.complement(hull),
+            None => Self::default(),
+        }
+    }
+
+    /// The IDs within `bounds` that are not in the set.
+    pub fn complement(&self, bounds: IdRange) -> Self {
+        let (lower, upper) = bounds.into_inner();
+        let mut ranges = Vec::new();
+        let mut next = Some(lower);
+
+        for r in &self.ranges {
+            let Some(start) = next else { break };
+            if *r.start() > upper {
+                break;
+            }
+
+            if *r.start() > start {
+                ranges.push(start..=r.start() - 1);
+            }
+            // Nothing is left once a range reaches the upper bound,
+            // which also covers ranges that end at `Id::MAX`.
+            next = (*r.end() < upper).then(|| Id::max(start, r.end() + 1));
+        }
+
+        if let Some(start) = next
+            && start <= upper
+        {
+            ranges.push(start..=upper);
+        }
+
+        Self { ranges }
+    }
+
+    /// The number of IDs in the set. This can be one more than fits in
+    /// an `Id`.
+    pub fn cardinality(&self) -> u128 {
+        self.ranges
+            .iter()
+            .map(|r| u128::from(r.end() - r.start()) + 1)
+            .sum()
+    }
+}
+
+/// One `a-b` line per range, like the fresh ranges in the input.
+impl fmt::Display for IntervalSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, r) in self.ranges.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}-{}", r.start(), r.end())?;
+        }
+        Ok(())
+    }
+}
+
+impl FromIterator<IdRange> for IntervalSet {
+    fn from_iter<I: IntoIterator<Item = IdRange>>(iter: I) -> Self {
+        let mut ranges = iter
+            .into_iter()
+            .filter(|r| !r.is_empty())
+            .collect::<Vec<_>>();
+        ranges.sort_by_key(|r| *r.start());
+
+        reduce_overlaps(&mut ranges);
+        Self { ranges }
+    }
+}
+
+/// Merges overlapping and touching ranges, which must be sorted by
+/// their start. Returns whether any were merged.
+fn reduce_overlaps(fresh_ranges: &mut Vec<IdRange>) -> bool {
+    let mut did_reduction = false;
+    let mut reduced = Vec::with_capacity(fresh_ranges.len());
+
+    if let [head, candidates @ ..] = &**fresh_ranges {
+        let mut w = head.clone();
+
+        for c in candidates {
+            let c = c.clone();
+
+            if w.end().checked_add(1).is_none_or(|e| *c.start() <= e) {
+                did_reduction = true;
+                let e = Id::max(*w.end(), *c.end());
+                w = *w.start()..=e;
+            } else {
+                reduced.push(w);
+                w = c;
+            }
+        }
+
+        reduced.push(w);
+    }
+
+    *fresh_ranges = reduced;
+    did_reduction
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::xorshift;
+    use std::collections::BTreeSet;
+
+    fn t_set(ranges: impl IntoIterator<Item = IdRange>) -> IntervalSet {
+        ranges.into_iter().collect()
+    }
+
+    #[test]
+    fn reduce_overlaps_exercise() {
+        fn t_reduce_overlaps(v: impl IntoIterator<Item = IdRange>) -> (bool, Vec<IdRange>) {
+            let mut v = v.into_iter().collect();
+            (reduce_overlaps(&mut v), v)
+        }
+
+        assert_eq!((false, vec![0..=10]), t_reduce_overlaps([0..=10]));
+        assert_eq!((true, vec![0..=15]), t_reduce_overlaps([0..=10, 5..=15]));
+        assert_eq!((true, vec![0..=10]), t_reduce_overlaps([0..=10, 5..=7]));
+        assert_eq!((true, vec![0..=15]), t_reduce_overlaps([0..=10, 11..=15]));
+        assert_eq!(
+            (false, vec![0..=10, 12..=15]),
+            t_reduce_overlaps([0..=10, 12..=15])
+        );
+        assert_eq!(
+            (true, vec![0..=Id::MAX]),
+            t_reduce_overlaps([0..=Id::MAX, 5..=7])
+        );
+    }
+
+    #[test]
+    fn insert_merges() {
+        let mut set = IntervalSet::new();
+        set.insert(10..=14);
+        set.insert(3..=5);
+        set.insert(16..=20);
+        assert_eq!([3..=5, 10..=14, 16..=20], set.ranges());
+
+        set.insert(12..=18);
+        assert_eq!([3..=5, 10..=20], set.ranges());
+
+        set.insert(6..=9);
+        assert_eq!([3..=20], set.ranges());
+
+        #[expect(clippy::reversed_empty_ranges)]
+        set.insert(30..=25);
+        set.insert(0..=0);
+        set.insert(Id::MAX..=Id::MAX);
+        assert_eq!([0..=0, 3..=20, Id::MAX..=Id::MAX], set.ranges());
+
+        set.insert(1..=Id::MAX - 1);
+        assert_eq!([0..=Id::MAX], set.ranges());
+        assert_eq!(1 << 64, set.cardinality());
+    }
+
+    #[test]
+    fn contains() {
+        let set = t_set([3..=5, 10..=14, 16..=20, 12..=18]);
+        let members = (0..25).filter(|&id| set.contains(id)).collect::<Vec<_>>();
+        assert_eq!(
+            vec![3, 4, 5, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20],
+            members
+        );
+        assert!(!IntervalSet::new().contains(0));
+    }
+
+    #[test]
+    fn bulk_membership() {
+        let set = t_set([3..=5, 10..=14, 16..=20, 12..=18]);
+        let ids = [1, 5, 8, 11, 17, 32];
+
+        assert_eq!(
+            vec![false, true, false, true, true, false],
+            set.contains_sorted(ids).collect::<Vec<_>>(),
+        );
+        assert_eq!(3, set.n_contained(&ids));
+        assert_eq!(3, set.n_contained(&[32, 17, 11, 8, 5, 1]));
+        assert_eq!(4, set.n_contained(&[5, 5, 5, 6, 20]));
+        assert_eq!(0, IntervalSet::new().n_contained(&ids));
+
+        // A deterministic jumble of IDs, both in their original order
+        // and sorted, against a linear scan of the unmerged ranges.
+        let ranges = [
+            0..=99,
+            150..=160,
+            155..=300,
+            301..=301,
+            500..=900,
+            1000..=1000,
+        ];
+        let set = t_set(ranges.clone());
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        let mut ids = (0..2000)
+            .map(|_| xorshift(&mut seed) % 1100)
+            .collect::<Vec<_>>();
+
+        let expected = ids
+            .iter()
+            .filter(|id| ranges.iter().any(|r| r.contains(id)))
+            .count();
+        assert_eq!(expected, set.n_contained(&ids));
+        ids.sort();
+        assert_eq!(expected, set.n_contained(&ids));
+    }
+
+    #[test]
+    #[should_panic(expected = "IDs are not sorted: 3 follows 5")]
+    fn contains_sorted_rejects_unsorted() {
+        t_set([0..=10]).contains_sorted([1, 5, 3]).for_each(drop);
+    }
+
+    #[test]
+    fn set_operations() {
+        let a = t_set([0..=10, 20..=30]);
+        let b = t_set([5..=25, 40..=45]);
+
+        assert_eq!([0..=30, 40..=45], a.union(&b).ranges());
+        assert_eq!([5..=10, 20..=25], a.intersection(&b).ranges());
+        assert_eq!([0..=4, 26..=30], a.difference(&b).ranges());
+        assert_eq!([11..=19, 31..=35], a.complement(0..=35).ranges());
+        assert_eq!([11..=19], a.complement(8..=22).ranges());
+
+        // Bounds starting after, or right next to, a range
+        assert_eq!([5..=10], t_set([0..=2]).complement(5..=10).ranges());
+        assert_eq!([5..=10], t_set([0..=4]).complement(5..=10).ranges());
+        assert_eq!([6..=10], t_set([0..=5]).complement(5..=10).ranges());
+        assert_eq!(
+            [5..=6, 9..=10],
+            t_set([0..=2, 7..=8]).complement(5..=10).ranges()
+        );
+        assert_eq!([5..=10], t_set([11..=12]).complement(5..=10).ranges());
+        assert_eq!(
+            IntervalSet::new(),
+            t_set([0..=4, 5..=20]).complement(5..=10)
+        );
+        assert_eq!(
+            [5..=Id::MAX - 1],
+            t_set([0..=2, Id::MAX..=Id::MAX])
+                .complement(5..=Id::MAX)
+                .ranges()
+        );
+        assert_eq!(
+            [0..=Id::MAX],
+            IntervalSet::new().complement(0..=Id::MAX).ranges()
+        );
+        assert_eq!(
+            IntervalSet::new(),
+            t_set([0..=Id::MAX]).complement(0..=Id::MAX)
+        );
+        assert_eq!(22, a.cardinality());
+
+        assert_eq!(Some(0..=30), a.hull());
+        assert_eq!([11..=19], a.gaps().ranges());
+        assert_eq!(IntervalSet::new(), t_set([5..=9]).gaps());
+        assert_eq!(None, IntervalSet::new().hull());
+        assert_eq!(IntervalSet::new(), IntervalSet::new().gaps());
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        const UNIVERSE: Id = 64;
+
+        fn members(set: &IntervalSet) -> BTreeSet<Id> {
+            (0..UNIVERSE).filter(|&id| set.contains(id)).collect()
+        }
+
+        fn canonical(set: &IntervalSet) -> bool {
+            set.ranges()
+                .windows(2)
+                .all(|w| w[0].end() + 1 < *w[1].start())
+                && set.ranges().iter().all(|r| !r.is_empty())
+        }
+
+        // Deterministic random ranges within the universe
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        let mut random_set = || {
+            let ranges = (0..5)
+                .map(|_| {
+                    let seed = xorshift(&mut seed);
+                    let start = seed % UNIVERSE;
+                    let len = (seed >> 16) % 12;
+                    start..=Id::min(start + len, UNIVERSE - 1)
+                })
+                .collect::<Vec<_>>();
+
+            let mut inserted = IntervalSet::new();
+            for r in &ranges {
+                inserted.insert(r.clone());
+            }
+            let collected = t_set(ranges);
+            assert_eq!(collected, inserted);
+            collected
+        };
+
+        for _ in 0..200 {
+            let (a, b) = (random_set(), random_set());
+            let (ma, mb) = (members(&a), members(&b));
+
+            for (set, expected) in [
+                (a.union(&b), &ma | &mb),
+                (a.intersection(&b), &ma & &mb),
+                (a.difference(&b), &ma - &mb),
+                (
+                    a.complement(0..=UNIVERSE - 1),
+                    (0..UNIVERSE).filter(|id| !ma.contains(id)).collect(),
+                ),
+            ] {
+                assert!(canonical(&set), "{set:?}");
+                assert_eq!(expected, members(&set));
+                assert_eq!(expected.len() as u128, set.cardinality());
+            }
+
+            // Bounds starting before, inside, next to and after the
+            // ranges
+            for lower in (0..UNIVERSE).step_by(3) {
+                for upper in (lower..UNIVERSE).step_by(5) {
+                    let set = a.complement(lower..=upper);
+                    let expected = (lower..=upper)
+                        .filter(|id| !ma.contains(id))
+                        .collect::<BTreeSet<_>>();
+
+                    assert!(canonical(&set), "{set:?}");
+                    assert_eq!(expected, members(&set), "{a:?} {lower}-{upper}");
+                }
+            }
+        }
+    }
+}
@@ -1,4 +1,15 @@
-use std::ops;
+use interval_set::IntervalSet;
+use std::{fmt, ops};
+
+mod interval_set;
+
+// Not needed for the puzzle answers
+#[cfg_attr(not(test), expect(dead_code))]
+mod audit;
+#[cfg_attr(not(test), expect(dead_code))]
+mod database;
+#[cfg_attr(not(test), expect(dead_code))]
+mod spans;
 
 const INPUT: &str = include_str!("../input.txt");
 
@@ -22,72 +33,158 @@ type Id = u64;
 type IdRange = ops::RangeInclusive<u64>;
 
 fn n_fresh_ingredients(s: &str) -> usize {
-    let mut ls = s.lines();
+    let input = parse_input(s);
 
-    let fresh_ranges = extract_ranges(&mut ls);
+    let fresh = input.fresh_ranges().collect::<IntervalSet>();
 
-    ls.map(|l| l.parse::<Id>().expect("invalid id"))
-        .filter(|id| fresh_ranges.iter().any(|r| r.contains(id)))
-        .count()
+    fresh.n_contained(&input.ids)
 }
 
 fn n_possible_fresh_ingredients(s: &str) -> usize {
-    let mut ls = s.lines();
+    fresh_database(s)
+        .cardinality()
+        .try_into()
+        .expect("too many fresh ingredients")
+}
+
+/// The merged fresh ranges from the top of the input.
+fn fresh_database(s: &str) -> IntervalSet {
+    parse_input(s).fresh_ranges().collect()
+}
 
-    let mut fresh_ranges = extract_ranges(&mut ls);
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Input {
+    /// Each fresh range with its 1-based line number, in input order
+    ranges: Vec<(usize, IdRange)>,
+    ids: Vec<Id>,
+}
 
-    fresh_ranges.sort_by_key(|r| *r.start());
+impl Input {
+    fn fresh_ranges(&self) -> impl Iterator<Item = IdRange> {
+        self.ranges.iter().map(|(_, r)| r.clone())
+    }
+}
+
+/// Panics with the line and section of any malformed line.
+fn parse_input(s: &str) -> Input {
+    try_parse_input(s).unwrap_or_else(|e| panic!("{e}"))
+}
 
-    // Shouldn't actually ever loop a second time, but whatever.
-    loop {
-        // eprintln!("---");
+/// The fresh ranges, then a blank line, then the ingredient IDs.
+/// Anything after a `#` is a comment, and lines with only a comment
+/// are ignored entirely.
+fn try_parse_input(s: &str) -> Result<Input, ParseError> {
+    let mut input = Input::default();
+    let mut section = Section::FreshRanges;
 
-        if !reduce_overlaps(&mut fresh_ranges) {
-            break;
+    for (line_idx, l) in s.lines().enumerate() {
+        let line = line_idx + 1;
+        let error = |kind| ParseError {
+            line,
+            section,
+            kind,
+        };
+
+        let (l, comment) = l.split_once('#').map_or((l, false), |(l, _)| (l, true));
+        let l = l.trim_end();
+        if l.is_empty() {
+            if !comment {
+                section = Section::Ingredients;
+            }
+            continue;
+        }
+
+        match section {
+            Section::FreshRanges => match parse_range(l) {
+                Some(r) if r.is_empty() => {
+                    let (start, end) = r.into_inner();
+                    return Err(error(ParseErrorKind::ReversedRange { start, end }));
+                }
+                Some(r) => input.ranges.push((line, r)),
+                None if l.parse::<Id>().is_ok() => {
+                    return Err(error(ParseErrorKind::MissingSeparator));
+                }
+                None => return Err(error(ParseErrorKind::MalformedRange(l.into()))),
+            },
+            Section::Ingredients => match l.parse::<Id>() {
+                Ok(id) => input.ids.push(id),
+                Err(_) if parse_range(l).is_some() => {
+                    return Err(error(ParseErrorKind::UnexpectedRange));
+                }
+                Err(_) => return Err(error(ParseErrorKind::InvalidId(l.into()))),
+            },
         }
     }
 
-    fresh_ranges.into_iter().map(|r| r.count()).sum()
+    Ok(input)
 }
 
-fn extract_ranges<'a>(ls: impl IntoIterator<Item = &'a str>) -> Vec<IdRange> {
-    ls.into_iter()
-        .take_while(|l| !l.is_empty())
-        .map(|l| {
-            let (l, u) = l.split_once("-").expect("malformed fresh range");
-            let [l, u] = [l, u].map(|i| i.parse::<Id>().expect("invalid fresh id"));
-            l..=u
-        })
-        .collect()
+/// `None` if the range isn't two IDs separated by `-`.
+fn parse_range(l: &str) -> Option<IdRange> {
+    let (l, u) = l.split_once("-")?;
+    Some(l.parse().ok()?..=u.parse().ok()?)
 }
 
-fn reduce_overlaps(fresh_ranges: &mut Vec<IdRange>) -> bool {
-    let mut did_reduction = false;
-    let mut reduced = Vec::with_capacity(fresh_ranges.len());
-
-    if let [head, candidates @ ..] = &**fresh_ranges {
-        let mut w = head.clone();
-
-        for c in candidates {
-            let c = c.clone();
-
-            if w.contains(c.start()) {
-                // eprintln!("{w:015?} ∩ {c:015?}");
-                did_reduction = true;
-                let e = Id::max(*w.end(), *c.end());
-                w = *w.start()..=e;
-            } else {
-                // eprintln!("{w:015?} ∩⃠ {c:015?}");
-                reduced.push(w);
-                w = c;
-            }
-        }
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Section {
+    FreshRanges,
+    Ingredients,
+}
 
-        reduced.push(w);
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Section::FreshRanges => write!(f, "fresh ranges"),
+            Section::Ingredients => write!(f, "ingredients"),
+        }
     }
+}
 
-    *fresh_ranges = reduced;
-    did_reduction
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ParseError {
+    /// 1-based
+    line: usize,
+    section: Section,
+    kind: ParseErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseErrorKind {
+    MalformedRange(String),
+    ReversedRange {
+        start: Id,
+        end: Id,
+    },
+    /// An ID before the blank line that ends the fresh ranges
+    MissingSeparator,
+    InvalidId(String),
+    /// A range after the blank line that ends the fresh ranges
+    UnexpectedRange,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self {
+            line,
+            section,
+            kind,
+        } = self;
+        write!(f, "Line {line} in the {section}: ")?;
+
+        match kind {
+            ParseErrorKind::MalformedRange(l) => write!(f, "Malformed fresh range `{l}`"),
+            ParseErrorKind::ReversedRange { start, end } => {
+                write!(f, "Range {start}-{end} is reversed")
+            }
+            ParseErrorKind::MissingSeparator => {
+                write!(f, "Found an ID, but no blank line after the fresh ranges")
+            }
+            ParseErrorKind::InvalidId(l) => write!(f, "Invalid ingredient ID `{l}`"),
+            ParseErrorKind::UnexpectedRange => {
+                write!(f, "Found a fresh range after the blank line")
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -96,6 +193,14 @@ mod test {
 
     const EXAMPLE: &str = include_str!("../example.txt");
 
+    /// A deterministic stream of pseudo-random numbers
+    pub fn xorshift(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
     #[test]
     fn part1_example() {
         assert_eq!(3, n_fresh_ingredients(EXAMPLE));
@@ -107,14 +212,48 @@ mod test {
     }
 
     #[test]
-    fn reduce_overlaps_exercise() {
-        fn t_reduce_overlaps(v: impl IntoIterator<Item = IdRange>) -> (bool, Vec<IdRange>) {
-            let mut v = v.into_iter().collect();
-            (reduce_overlaps(&mut v), v)
+    fn comments() {
+        let s = "# Fresh\n3-5 # first\n10-14\n# still fresh\n16-20\n12-18\n\n1\n5 # fresh\n8\n11\n17\n32\n";
+        let input = parse_input(s);
+        assert_eq!(
+            vec![2, 3, 5, 6],
+            input.ranges.iter().map(|(l, _)| *l).collect::<Vec<_>>()
+        );
+        assert_eq!(vec![1, 5, 8, 11, 17, 32], input.ids);
+        assert_eq!(3, n_fresh_ingredients(s));
+        assert_eq!(14, n_possible_fresh_ingredients(s));
+
+        // Only the fresh ranges
+        assert_eq!(14, n_possible_fresh_ingredients("3-5\n10-14\n16-20\n12-18"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        fn error(s: &str) -> (usize, Section, ParseErrorKind) {
+            let e = try_parse_input(s).expect_err("Should not parse");
+            (e.line, e.section, e.kind)
         }
 
-        assert_eq!((false, vec![0..=10]), t_reduce_overlaps([0..=10]));
-        assert_eq!((true, vec![0..=15]), t_reduce_overlaps([0..=10, 5..=15]));
-        assert_eq!((true, vec![0..=10]), t_reduce_overlaps([0..=10, 5..=7]));
+        use ParseErrorKind::*;
+        use Section::*;
+
+        assert_eq!((2, FreshRanges, MissingSeparator), error("3-5\n1\n5"));
+        assert_eq!(
+            (3, FreshRanges, ReversedRange { start: 20, end: 10 }),
+            error("3-5\n#\n20-10")
+        );
+        assert_eq!((1, FreshRanges, MalformedRange("3_5".into())), error("3_5"));
+        assert_eq!((1, FreshRanges, MalformedRange("3-x".into())), error("3-x"));
+        assert_eq!((4, Ingredients, UnexpectedRange), error("3-5\n\n1\n4-6"));
+        assert_eq!((3, Ingredients, InvalidId("x".into())), error("3-5\n\nx"));
+
+        assert_eq!(
+            "Line 3 in the fresh ranges: Range 20-10 is reversed",
+            try_parse_input("3-5\n#\n20-10").unwrap_err().to_string(),
+        );
+        assert_eq!(
+            "Line 2 in the fresh ranges: Found an ID, but no blank line after the fresh ranges",
+            try_parse_input("3-5\n1").unwrap_err().to_string(),
+        );
     }
 }
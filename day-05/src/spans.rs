@@ -0,0 +1,74 @@
+//! The shape of the merged fresh ranges.
+
+use crate::{IdRange, fresh_database};
+
+/// The runs of spoiled IDs between the fresh ranges, in order.
+pub fn gaps(s: &str) -> Vec<IdRange> {
+    fresh_database(s).gaps().ranges().to_vec()
+}
+
+/// The longest run of fresh IDs, or the first of them if there's a tie.
+pub fn longest_fresh_span(s: &str) -> Option<IdRange> {
+    longest(fresh_database(s).ranges())
+}
+
+/// The longest run of spoiled IDs between two fresh ranges, or the
+/// first of them if there's a tie.
+pub fn longest_gap(s: &str) -> Option<IdRange> {
+    longest(fresh_database(s).gaps().ranges())
+}
+
+/// The merged fresh ranges, in the same `a-b` format as the input.
+pub fn merged_database(s: &str) -> String {
+    fresh_database(s).to_string()
+}
+
+fn longest(ranges: &[IdRange]) -> Option<IdRange> {
+    // `max_by_key` prefers the last of equal elements
+    ranges
+        .iter()
+        .rev()
+        .max_by_key(|r| r.end() - r.start())
+        .cloned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = include_str!("../example.txt");
+
+    #[test]
+    fn example() {
+        assert_eq!(vec![6..=9], gaps(EXAMPLE));
+        assert_eq!(Some(10..=20), longest_fresh_span(EXAMPLE));
+        assert_eq!(Some(6..=9), longest_gap(EXAMPLE));
+        assert_eq!("3-5\n10-20", merged_database(EXAMPLE));
+    }
+
+    #[test]
+    fn ties_prefer_first() {
+        let s = "1-2\n5-6\n9-10\n14-15\n\n1";
+        assert_eq!(vec![3..=4, 7..=8, 11..=13], gaps(s));
+        assert_eq!(Some(1..=2), longest_fresh_span(s));
+        assert_eq!(Some(11..=13), longest_gap(s));
+    }
+
+    #[test]
+    fn merged_database_round_trips() {
+        let merged = merged_database(EXAMPLE);
+        assert_eq!(merged, merged_database(&merged));
+        assert_eq!(
+            crate::n_possible_fresh_ingredients(EXAMPLE),
+            crate::n_possible_fresh_ingredients(&merged),
+        );
+    }
+
+    #[test]
+    fn single_range() {
+        assert_eq!(Vec::<IdRange>::new(), gaps("5-9\n"));
+        assert_eq!(None, longest_gap("5-9\n"));
+        assert_eq!(None, longest_fresh_span("\n1"));
+        assert_eq!("", merged_database("\n1"));
+    }
+}
@@ -13,86 +13,145 @@ fn main() {
 }
 
 fn cephalopod_math_sum(s: &str) -> u64 {
-    let mut numbers = s.lines();
-    let operations = numbers.next_back().expect("No operations");
-    let operations = operations.split_ascii_whitespace();
-
-    let mut numbers = numbers.map(|l| {
-        l.split_ascii_whitespace()
-            .map(|n| n.parse::<u64>().expect("Invalid number"))
-    });
-
-    let head = numbers
-        .next()
-        .expect("Need initial values")
-        .collect::<Vec<_>>();
+    parse_worksheet(s)
+        .iter()
+        .map(|p| p.operator.apply(p.row_numbers()))
+        .sum()
+}
 
-    let results = numbers.fold(head, |mut acc, n| {
-        for ((a, n), op) in acc.iter_mut().zip(n).zip(operations.clone()) {
-            if op == "+" {
-                *a += n;
-            } else {
-                *a *= n;
-            }
-        }
-        acc
-    });
+fn cephalopod_math_explained_sum(s: &str) -> u64 {
+    parse_worksheet(s)
+        .iter()
+        .map(|p| p.operator.apply(p.column_numbers()))
+        .sum()
+}
 
-    results.into_iter().sum()
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Operator {
+    Add,
+    Multiply,
 }
 
-fn cephalopod_math_explained_sum(s: &str) -> u64 {
-    let mut numbers = s.lines();
-    let operations = numbers.next_back().expect("No operations");
+impl Operator {
+    fn apply(self, numbers: impl IntoIterator<Item = u64>) -> u64 {
+        let op = match self {
+            Operator::Add => ops::Add::add,
+            Operator::Multiply => ops::Mul::mul,
+        };
+
+        numbers
+            .into_iter()
+            .reduce(op)
+            .expect("Didn't perform any operations")
+    }
+}
+
+/// One problem from the worksheet, still laid out as it was written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Problem {
+    /// The character columns the problem occupies
+    columns: ops::Range<usize>,
+    operator: Operator,
+    /// The digit in each row and column of the problem, `None` where
+    /// it's blank. Indexed by `cells[y][x]`, relative to the problem.
+    cells: Vec<Vec<Option<u8>>>,
+}
+
+impl Problem {
+    /// Each row is a number, read left-to-right.
+    fn row_numbers(&self) -> impl Iterator<Item = u64> {
+        self.cells
+            .iter()
+            .filter_map(|row| digits_to_number(row.iter()))
+    }
+
+    /// Each column is a number, read top-to-bottom, and the columns
+    /// are taken right-to-left.
+    fn column_numbers(&self) -> impl Iterator<Item = u64> {
+        let width = self.columns.len();
+
+        (0..width)
+            .rev()
+            .filter_map(|x| digits_to_number(self.cells.iter().map(|row| &row[x])))
+    }
+}
 
-    // Convert from strings to Vec / Option / u64
-    let numbers = numbers
+/// The most significant digit comes first, but blanks can come before
+/// or after the digits, so they are skipped rather than treated as
+/// zeroes. `None` if there are no digits.
+fn digits_to_number<'a>(digits: impl IntoIterator<Item = &'a Option<u8>>) -> Option<u64> {
+    digits
+        .into_iter()
+        .flatten()
+        .map(|&d| u64::from(d))
+        .reduce(|l, r| l * 10 + r)
+}
+
+/// Rows of digits with a row of operators at the bottom. Problems are
+/// separated by columns that are blank in every row of digits.
+fn parse_worksheet(s: &str) -> Vec<Problem> {
+    let mut lines = s.lines();
+    let operators = lines.next_back().expect("No operations");
+
+    let rows = lines
         .map(|l| {
             l.chars()
-                .map(|c| c.to_digit(10).map(u64::from))
+                .map(|c| match c {
+                    ' ' => None,
+                    c => {
+                        let d = c.to_digit(10).expect("Invalid number");
+                        Some(d as u8)
+                    }
+                })
                 .collect::<Vec<_>>()
         })
         .collect::<Vec<_>>();
 
-    // Find the bounds of the 2D Vec
-    let h = numbers.len();
-    let w = numbers.first().map(|n| n.len()).unwrap_or(0);
-
-    // Build up the numbers by walking right-to-left The most
-    // significant number occurs before other digits, but that doesn't
-    // mean that it is on the first line, so we keep the `None`
-    // instead of making everything a zero.
-    let mut numbers = (0..w).rev().map(|x| {
-        (0..h)
-            .map(|y| numbers[y][x])
-            .reduce(|l, r| match (l, r) {
-                (None, None) => None,
-                (None, v) | (v, None) => v,
-                (Some(l), Some(r)) => Some(l * 10 + r),
+    // Lines can be different lengths, as trailing blanks may be missing
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let cell = |row: &[Option<u8>], x: usize| row.get(x).copied().flatten();
+    let is_separator = |x| rows.iter().all(|row| cell(row, x).is_none());
+
+    let mut problems = Vec::new();
+    let mut x = 0;
+
+    while x < width {
+        if is_separator(x) {
+            x += 1;
+            continue;
+        }
+
+        let start = x;
+        while x < width && !is_separator(x) {
+            x += 1;
+        }
+        let columns = start..x;
+
+        let operator = operators
+            .chars()
+            .skip(start)
+            .take(columns.len())
+            .find(|c| !c.is_ascii_whitespace())
+            .map(|c| match c {
+                '+' => Operator::Add,
+                '*' => Operator::Multiply,
+                c => panic!("Unknown operator `{c}`"),
             })
-            .flatten()
-    });
-
-    operations
-        .split_ascii_whitespace()
-        .rev()
-        .map(|op| {
-            let op = if op == "+" {
-                ops::Add::add
-            } else {
-                ops::Mul::mul
-            };
-
-            // A whole column of `None` corresponds to the end of a
-            // problem, so we group by that.
-            numbers
-                .by_ref()
-                .take_while(|n| n.is_some())
-                .flatten()
-                .reduce(op)
-                .expect("Didn't perform any operations")
-        })
-        .sum()
+            .expect("Missing operator");
+
+        let cells = rows
+            .iter()
+            .map(|row| columns.clone().map(|x| cell(row, x)).collect())
+            .collect();
+
+        problems.push(Problem {
+            columns,
+            operator,
+            cells,
+        });
+    }
+
+    problems
 }
 
 #[cfg(test)]
@@ -110,4 +169,52 @@ mod test {
     fn part2_example() {
         assert_eq!(3263827, cephalopod_math_explained_sum(EXAMPLE));
     }
+
+    #[test]
+    fn parse_example() {
+        let problems = parse_worksheet(EXAMPLE);
+
+        assert_eq!(
+            vec![
+                (0..3, Operator::Multiply),
+                (4..7, Operator::Add),
+                (8..11, Operator::Multiply),
+                (12..15, Operator::Add),
+            ],
+            problems
+                .iter()
+                .map(|p| (p.columns.clone(), p.operator))
+                .collect::<Vec<_>>(),
+        );
+
+        assert_eq!(
+            vec![
+                vec![Some(1), Some(2), Some(3)],
+                vec![None, Some(4), Some(5)],
+                vec![None, None, Some(6)],
+            ],
+            problems[0].cells,
+        );
+
+        let p = &problems[3];
+        assert_eq!(vec![64, 23, 314], p.row_numbers().collect::<Vec<_>>());
+        assert_eq!(vec![4, 431, 623], p.column_numbers().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn ragged_lines() {
+        // Trailing blanks are missing from the end of the lines
+        let s = "1 23\n4 5\n6  7\n+ *";
+        let problems = parse_worksheet(s);
+
+        assert_eq!(
+            vec![0..1, 2..4],
+            problems
+                .iter()
+                .map(|p| p.columns.clone())
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(1 + 4 + 6 + 23 * 5 * 7, cephalopod_math_sum(s));
+        assert_eq!(146 + 37 * 25, cephalopod_math_explained_sum(s));
+    }
 }